@@ -0,0 +1,16 @@
+-- Long-lived refresh tokens issued at login. Only the SHA-256 hash is stored.
+-- Every token created by rotating another one keeps the same family_id.
+
+CREATE TABLE
+    IF NOT EXISTS refresh_tokens (
+        id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+        user_id BIGINT NOT NULL,
+        family_id CHAR(36) NOT NULL,
+        token_hash CHAR(64) NOT NULL UNIQUE,
+        expires_at TIMESTAMP NOT NULL,
+        revoked_at TIMESTAMP NULL DEFAULT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        KEY `refresh_tokens_user_id` (`user_id`),
+        KEY `refresh_tokens_family_id` (`family_id`),
+        CONSTRAINT `refresh_tokens_ibfk_1` FOREIGN KEY (`user_id`) REFERENCES `login` (`id`) ON DELETE CASCADE
+    );
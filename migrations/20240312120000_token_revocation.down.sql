@@ -0,0 +1,2 @@
+ALTER TABLE login DROP COLUMN token_version;
+DROP TABLE IF EXISTS revoked_tokens;
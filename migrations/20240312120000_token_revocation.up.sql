@@ -0,0 +1,12 @@
+-- Access tokens revoked before their exp (logout). Rows can be dropped once expires_at has passed.
+CREATE TABLE
+    IF NOT EXISTS revoked_tokens (
+        jti CHAR(36) PRIMARY KEY NOT NULL,
+        user_id BIGINT NOT NULL,
+        expires_at TIMESTAMP NOT NULL,
+        revoked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        KEY `revoked_tokens_expires_at` (`expires_at`)
+    );
+
+-- Bumped on "log out everywhere"; tokens carrying an older `ver` claim are rejected.
+ALTER TABLE login ADD COLUMN token_version INT NOT NULL DEFAULT 0;
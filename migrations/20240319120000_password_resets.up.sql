@@ -0,0 +1,12 @@
+-- Single-use password reset tokens. Only the SHA-256 hash is stored.
+CREATE TABLE
+    IF NOT EXISTS password_resets (
+        id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+        user_id BIGINT NOT NULL,
+        token_hash CHAR(64) NOT NULL UNIQUE,
+        expires_at TIMESTAMP NOT NULL,
+        used_at TIMESTAMP NULL DEFAULT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        KEY `password_resets_user_id` (`user_id`),
+        CONSTRAINT `password_resets_ibfk_1` FOREIGN KEY (`user_id`) REFERENCES `login` (`id`) ON DELETE CASCADE
+    );
@@ -0,0 +1,16 @@
+-- New accounts start unverified. Accounts that already exist are treated as verified.
+ALTER TABLE login ADD COLUMN email_verified_at TIMESTAMP NULL DEFAULT NULL;
+UPDATE login SET email_verified_at = CURRENT_TIMESTAMP;
+
+-- Single-use email verification tokens. Only the SHA-256 hash is stored.
+CREATE TABLE
+    IF NOT EXISTS email_verifications (
+        id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+        user_id BIGINT NOT NULL,
+        token_hash CHAR(64) NOT NULL UNIQUE,
+        expires_at TIMESTAMP NOT NULL,
+        used_at TIMESTAMP NULL DEFAULT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        KEY `email_verifications_user_id` (`user_id`),
+        CONSTRAINT `email_verifications_ibfk_1` FOREIGN KEY (`user_id`) REFERENCES `login` (`id`) ON DELETE CASCADE
+    );
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS totp_recovery_codes;
+ALTER TABLE login
+    DROP COLUMN totp_secret,
+    DROP COLUMN totp_enabled_at,
+    DROP COLUMN totp_last_step;
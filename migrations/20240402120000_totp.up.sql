@@ -0,0 +1,19 @@
+-- TOTP two-factor authentication. totp_secret is set on enrollment and only
+-- enforced once totp_enabled_at is set by confirming a first code.
+-- totp_last_step is the time step of the last accepted code, so codes can't be replayed.
+ALTER TABLE login
+    ADD COLUMN totp_secret VARCHAR(64) NULL DEFAULT NULL,
+    ADD COLUMN totp_enabled_at TIMESTAMP NULL DEFAULT NULL,
+    ADD COLUMN totp_last_step BIGINT NOT NULL DEFAULT 0;
+
+-- One-time recovery codes. Only the SHA-256 hash is stored.
+CREATE TABLE
+    IF NOT EXISTS totp_recovery_codes (
+        id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+        user_id BIGINT NOT NULL,
+        code_hash CHAR(64) NOT NULL,
+        used_at TIMESTAMP NULL DEFAULT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        KEY `totp_recovery_codes_user_id` (`user_id`),
+        CONSTRAINT `totp_recovery_codes_ibfk_1` FOREIGN KEY (`user_id`) REFERENCES `login` (`id`) ON DELETE CASCADE
+    );
@@ -0,0 +1,16 @@
+-- Personal access tokens for service integrations. Only the SHA-256 hash is stored.
+-- scopes is a space separated list, e.g. 'tickets:read comments:write'.
+CREATE TABLE
+    IF NOT EXISTS api_tokens (
+        id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+        user_id BIGINT NOT NULL,
+        name VARCHAR(255) NOT NULL,
+        token_hash CHAR(64) NOT NULL UNIQUE,
+        scopes VARCHAR(255) NOT NULL,
+        expires_at TIMESTAMP NULL DEFAULT NULL,
+        last_used_at TIMESTAMP NULL DEFAULT NULL,
+        revoked_at TIMESTAMP NULL DEFAULT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        KEY `api_tokens_user_id` (`user_id`),
+        CONSTRAINT `api_tokens_ibfk_1` FOREIGN KEY (`user_id`) REFERENCES `login` (`id`) ON DELETE CASCADE
+    );
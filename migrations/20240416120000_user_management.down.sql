@@ -0,0 +1,3 @@
+ALTER TABLE login
+    DROP COLUMN deactivated_at,
+    DROP COLUMN password_reset_required;
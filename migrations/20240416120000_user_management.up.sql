@@ -0,0 +1,6 @@
+-- Accounts disabled by an admin. auth_guard and login refuse them while set.
+-- password_reset_required is set when an admin forces a password reset and
+-- blocks logging in until the user chose a new password.
+ALTER TABLE login
+    ADD COLUMN deactivated_at TIMESTAMP NULL DEFAULT NULL,
+    ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Address a verification token confirms when the user changes their email.
+-- NULL for the sign-up verification of the account's current email.
+ALTER TABLE email_verifications ADD COLUMN email VARCHAR(255) NULL DEFAULT NULL;
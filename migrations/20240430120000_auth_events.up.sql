@@ -0,0 +1,24 @@
+-- Append-only log of authentication events. user_id is kept without a foreign
+-- key so the history survives the account; `email` is what was typed on a
+-- failed login. actor_id is the admin behind admin actions.
+CREATE TABLE
+    IF NOT EXISTS auth_events (
+        id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+        event VARCHAR(64) NOT NULL,
+        user_id BIGINT NULL DEFAULT NULL,
+        actor_id BIGINT NULL DEFAULT NULL,
+        email VARCHAR(255) NULL DEFAULT NULL,
+        ip VARCHAR(45) NOT NULL,
+        user_agent VARCHAR(512) NOT NULL DEFAULT '',
+        detail VARCHAR(255) NULL DEFAULT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        KEY `auth_events_user_id` (`user_id`, `created_at`),
+        KEY `auth_events_event` (`event`, `created_at`),
+        KEY `auth_events_created_at` (`created_at`)
+    );
+
+CREATE TRIGGER auth_events_no_update BEFORE UPDATE ON auth_events
+    FOR EACH ROW SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'auth_events is append-only';
+
+CREATE TRIGGER auth_events_no_delete BEFORE DELETE ON auth_events
+    FOR EACH ROW SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'auth_events is append-only';
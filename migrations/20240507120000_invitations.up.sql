@@ -0,0 +1,16 @@
+-- Invitations for invite-only registration. The role is given to the account
+-- registered with the invitation. Only the SHA-256 hash of the token is stored.
+CREATE TABLE
+    IF NOT EXISTS invitations (
+        id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+        email VARCHAR(255) NOT NULL,
+        role VARCHAR(32) NOT NULL,
+        token_hash CHAR(64) NOT NULL UNIQUE,
+        invited_by BIGINT NULL DEFAULT NULL,
+        expires_at TIMESTAMP NOT NULL,
+        accepted_at TIMESTAMP NULL DEFAULT NULL,
+        revoked_at TIMESTAMP NULL DEFAULT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        KEY `invitations_email` (`email`),
+        CONSTRAINT `invitations_ibfk_1` FOREIGN KEY (`invited_by`) REFERENCES `login` (`id`) ON DELETE SET NULL
+    );
@@ -0,0 +1,3 @@
+ALTER TABLE login
+    DROP INDEX `login_oidc_subject`,
+    DROP COLUMN oidc_subject;
@@ -0,0 +1,5 @@
+-- Single sign-on: the provider's `sub` for accounts that logged in through it.
+-- Accounts created by single sign-on have no password (NULL) until they reset one.
+ALTER TABLE login
+    ADD COLUMN oidc_subject VARCHAR(255) NULL DEFAULT NULL,
+    ADD UNIQUE KEY `login_oidc_subject` (`oidc_subject`);
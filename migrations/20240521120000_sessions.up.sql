@@ -0,0 +1,16 @@
+-- One row per login. The id is the family_id of the session's refresh tokens
+-- and the `sid` claim of its access tokens.
+CREATE TABLE
+    IF NOT EXISTS sessions (
+        id CHAR(36) PRIMARY KEY NOT NULL,
+        user_id BIGINT NOT NULL,
+        ip VARCHAR(45) NOT NULL,
+        user_agent VARCHAR(512) NOT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        last_seen_at TIMESTAMP NOT NULL,
+        -- pushed forward on every refresh, like the refresh token's own expiry
+        expires_at TIMESTAMP NOT NULL,
+        revoked_at TIMESTAMP NULL DEFAULT NULL,
+        KEY `sessions_user_id` (`user_id`),
+        CONSTRAINT `sessions_ibfk_1` FOREIGN KEY (`user_id`) REFERENCES `login` (`id`) ON DELETE CASCADE
+    );
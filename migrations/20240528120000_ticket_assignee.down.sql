@@ -0,0 +1,4 @@
+ALTER TABLE tickets
+    DROP FOREIGN KEY `tickets_assignee_fk`,
+    DROP KEY `tickets_assignee_id`,
+    DROP COLUMN assignee_id;
@@ -0,0 +1,6 @@
+-- The agent or admin working on a ticket. Unassigned when NULL, and again when
+-- the account is deleted.
+ALTER TABLE tickets
+    ADD COLUMN assignee_id BIGINT NULL DEFAULT NULL,
+    ADD KEY `tickets_assignee_id` (`assignee_id`),
+    ADD CONSTRAINT `tickets_assignee_fk` FOREIGN KEY (`assignee_id`) REFERENCES `login` (`id`) ON DELETE SET NULL;
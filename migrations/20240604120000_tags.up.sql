@@ -0,0 +1,18 @@
+-- Labels on tickets. Names are stored lowercased and can't contain commas,
+-- the ticket list's `tag` filter takes them comma separated.
+CREATE TABLE
+    IF NOT EXISTS tags (
+        id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+        name VARCHAR(64) NOT NULL UNIQUE,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE TABLE
+    IF NOT EXISTS ticket_tags (
+        ticket_id BIGINT NOT NULL,
+        tag_id BIGINT NOT NULL,
+        PRIMARY KEY (`ticket_id`, `tag_id`),
+        KEY `ticket_tags_tag_id` (`tag_id`),
+        CONSTRAINT `ticket_tags_ibfk_1` FOREIGN KEY (`ticket_id`) REFERENCES `tickets` (`id`) ON DELETE CASCADE,
+        CONSTRAINT `ticket_tags_ibfk_2` FOREIGN KEY (`tag_id`) REFERENCES `tags` (`id`) ON DELETE CASCADE
+    );
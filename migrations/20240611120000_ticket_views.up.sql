@@ -0,0 +1,15 @@
+-- Saved ticket list filters. Shared views are visible to everyone, private
+-- ones only to their owner. `filters` holds the list query parameters as JSON.
+CREATE TABLE
+    IF NOT EXISTS ticket_views (
+        id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+        owner_id BIGINT NOT NULL,
+        name VARCHAR(100) NOT NULL,
+        shared BOOLEAN NOT NULL DEFAULT FALSE,
+        filters TEXT NOT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+        KEY `ticket_views_owner_id` (`owner_id`),
+        KEY `ticket_views_shared` (`shared`),
+        CONSTRAINT `ticket_views_ibfk_1` FOREIGN KEY (`owner_id`) REFERENCES `login` (`id`) ON DELETE CASCADE
+    );
@@ -0,0 +1,3 @@
+ALTER TABLE tickets
+    DROP CHECK `tickets_status_check`,
+    DROP CHECK `tickets_priority_check`;
@@ -0,0 +1,32 @@
+-- Status and priority used to be free text. Normalise what is there
+-- ("In Progress", "HIGH", ...) and map the spellings we know of.
+UPDATE tickets SET
+    status = REPLACE(REPLACE(LOWER(TRIM(status)), ' ', '_'), '-', '_'),
+    priority = LOWER(TRIM(priority));
+
+UPDATE tickets SET status = CASE
+        WHEN status IN ('open', 'new') THEN 'created'
+        WHEN status IN ('inprogress', 'in_progess', 'started', 'working') THEN 'in_progress'
+        WHEN status IN ('done', 'fixed', 'solved') THEN 'resolved'
+        WHEN status IN ('close', 'closd') THEN 'closed'
+        ELSE status
+    END;
+
+UPDATE tickets SET priority = CASE
+        WHEN priority IN ('lo', 'lowest', 'minor') THEN 'low'
+        WHEN priority IN ('med', 'normal', 'medum') THEN 'medium'
+        WHEN priority IN ('hi', 'hgh', 'major') THEN 'high'
+        WHEN priority IN ('urgnt', 'urgnet', 'urgen', 'critical', 'highest') THEN 'urgent'
+        ELSE priority
+    END;
+
+-- Anything still unknown makes these fail instead of being guessed. Find the
+-- rows with
+--   SELECT id, status, priority FROM tickets
+--   WHERE status NOT IN ('created', 'in_progress', 'resolved', 'closed')
+--      OR priority NOT IN ('low', 'medium', 'high', 'urgent');
+-- fix them by hand and run the migration again; the updates above are safe to
+-- repeat.
+ALTER TABLE tickets
+    ADD CONSTRAINT `tickets_status_check` CHECK (status IN ('created', 'in_progress', 'resolved', 'closed')),
+    ADD CONSTRAINT `tickets_priority_check` CHECK (priority IN ('low', 'medium', 'high', 'urgent'));
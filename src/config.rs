@@ -1,22 +1,210 @@
+use axum_extra::extract::cookie::SameSite;
+
+use crate::utils::cookies::parse_same_site;
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_url: String,
     pub jwt_secret: String,
-    pub jwt_expires_in: String,
+    pub jwt_algorithm: String,
+    pub jwt_signing_key_file: Option<String>,
+    pub jwt_signing_key_id: Option<String>,
+    pub jwt_verification_keys: Vec<(String, String)>,
+    pub jwt_expires_in: chrono::Duration,
     pub jwt_maxage: i32,
+    pub cookie_secure: bool,
+    pub cookie_same_site: SameSite,
+    pub cookie_domain: Option<String>,
+    pub sliding_sessions: bool,
+    pub sliding_session_threshold: i64,
+    pub refresh_token_maxage: i64,
+    pub app_url: String,
+    pub mailer: String,
+    pub mailer_file: String,
+    pub password_reset_ttl: i64,
+    pub email_verification_ttl: i64,
+    pub unverified_login: String,
+    pub login_max_attempts: u32,
+    pub login_ip_max_attempts: u32,
+    pub login_backoff_seconds: u64,
+    pub login_lockout_seconds: u64,
+    pub totp_issuer: String,
+    pub password_hasher: String,
+    pub registration: String,
+    pub oidc_issuer: Option<String>,
+    pub oidc_client_id: Option<String>,
+    pub oidc_client_secret: Option<String>,
+    pub oidc_redirect_url: String,
+    pub oidc_scopes: String,
+    pub oidc_groups_claim: String,
+    pub oidc_role_mapping: Vec<(String, String)>,
+    pub oidc_default_role: String,
+    pub oidc_post_login_redirect: String,
+    pub invitation_ttl: i64,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    pub bcrypt_cost: u32,
+    pub password_min_length: usize,
+    pub password_min_entropy: f64,
+    pub breached_passwords_file: Option<String>,
 }
 
 impl Config {
     pub fn init() -> Config {
         let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+        // only required with HS256, see utils::keys
+        let jwt_secret = std::env::var("JWT_SECRET").unwrap_or_default();
+        // "HS256", "RS256" or "EdDSA"
+        let jwt_algorithm = std::env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string());
+        // PEM private key and its key id, for RS256 / EdDSA
+        let jwt_signing_key_file = std::env::var("JWT_SIGNING_KEY_FILE").ok();
+        let jwt_signing_key_id = std::env::var("JWT_SIGNING_KEY_ID").ok();
+        // "kid=public.pem,kid2=other.pem", all keys accepted and published in the JWKS
+        let jwt_verification_keys = std::env::var("JWT_VERIFICATION_KEYS").unwrap_or_default();
         let jwt_expires_in = std::env::var("JWT_EXPIRED_IN").expect("JWT_EXPIRED_IN must be set");
+        // access token cookie max-age, in minutes
         let jwt_maxage = std::env::var("JWT_MAXAGE").expect("JWT_MAXAGE must be set");
+        let cookie_secure = std::env::var("COOKIE_SECURE").unwrap_or_else(|_| "false".to_string());
+        // "Strict", "Lax" or "None" (None requires COOKIE_SECURE=true)
+        let cookie_same_site = std::env::var("COOKIE_SAME_SITE").unwrap_or_else(|_| "Lax".to_string());
+        let cookie_domain = std::env::var("COOKIE_DOMAIN").ok();
+        // renew the token cookie on requests made less than SLIDING_SESSION_THRESHOLD
+        // minutes before the access token expires
+        let sliding_sessions = std::env::var("SLIDING_SESSIONS").unwrap_or_else(|_| "false".to_string());
+        let sliding_session_threshold = std::env::var("SLIDING_SESSION_THRESHOLD").unwrap_or_else(|_| "15".to_string());
+        // in days
+        let refresh_token_maxage = std::env::var("REFRESH_TOKEN_MAXAGE").unwrap_or_else(|_| "30".to_string());
+        // frontend base url used in links sent by email
+        let app_url = std::env::var("APP_URL").unwrap_or_else(|_| "http://localhost:5173".to_string());
+        // "stdout" or "file"
+        let mailer = std::env::var("MAILER").unwrap_or_else(|_| "stdout".to_string());
+        let mailer_file = std::env::var("MAILER_FILE").unwrap_or_else(|_| "mail.log".to_string());
+        // in minutes
+        let password_reset_ttl = std::env::var("PASSWORD_RESET_TTL").unwrap_or_else(|_| "30".to_string());
+        // in hours
+        let email_verification_ttl = std::env::var("EMAIL_VERIFICATION_TTL").unwrap_or_else(|_| "24".to_string());
+        // "deny" refuses to log in unverified accounts, "limited" lets them in
+        // but keeps them out of everything except their own profile
+        let unverified_login = std::env::var("UNVERIFIED_LOGIN").unwrap_or_else(|_| "deny".to_string());
+        // failed logins before an account / a client IP gets locked out
+        let login_max_attempts = std::env::var("LOGIN_MAX_ATTEMPTS").unwrap_or_else(|_| "5".to_string());
+        let login_ip_max_attempts = std::env::var("LOGIN_IP_MAX_ATTEMPTS").unwrap_or_else(|_| "20".to_string());
+        // in seconds, doubled after every failed attempt
+        let login_backoff_seconds = std::env::var("LOGIN_BACKOFF_SECONDS").unwrap_or_else(|_| "1".to_string());
+        // in seconds
+        let login_lockout_seconds = std::env::var("LOGIN_LOCKOUT_SECONDS").unwrap_or_else(|_| "900".to_string());
+        // name shown in authenticator apps
+        let totp_issuer = std::env::var("TOTP_ISSUER").unwrap_or_else(|_| "Ticketing".to_string());
+        // "open" lets anyone register, "invite" requires an invitation from an admin
+        let registration = std::env::var("REGISTRATION").unwrap_or_else(|_| "open".to_string());
+        // in hours
+        let invitation_ttl = std::env::var("INVITATION_TTL").unwrap_or_else(|_| "72".to_string());
+        // single sign-on, enabled when OIDC_ISSUER is set
+        let oidc_issuer = std::env::var("OIDC_ISSUER").ok();
+        let oidc_client_id = std::env::var("OIDC_CLIENT_ID").ok();
+        // left out for public clients, PKCE protects the code exchange either way
+        let oidc_client_secret = std::env::var("OIDC_CLIENT_SECRET").ok();
+        let oidc_redirect_url = std::env::var("OIDC_REDIRECT_URL").unwrap_or_else(|_| "http://localhost:3000/api/oidc/callback".to_string());
+        let oidc_scopes = std::env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid email profile".to_string());
+        let oidc_groups_claim = std::env::var("OIDC_GROUPS_CLAIM").unwrap_or_else(|_| "groups".to_string());
+        // "group=role,other-group=role"
+        let oidc_role_mapping = std::env::var("OIDC_ROLE_MAPPING").unwrap_or_default();
+        let oidc_default_role = std::env::var("OIDC_DEFAULT_ROLE").unwrap_or_else(|_| "user".to_string());
+        // where the browser lands after logging in with the provider
+        let oidc_post_login_redirect = std::env::var("OIDC_POST_LOGIN_REDIRECT").unwrap_or_else(|_| app_url.clone());
+        // "argon2id" or "bcrypt" for new hashes, both are always accepted on login
+        let password_hasher = std::env::var("PASSWORD_HASHER").unwrap_or_else(|_| "argon2id".to_string());
+        // OWASP minimums for Argon2id
+        let argon2_memory_kib = std::env::var("ARGON2_MEMORY_KIB").unwrap_or_else(|_| "19456".to_string());
+        let argon2_iterations = std::env::var("ARGON2_ITERATIONS").unwrap_or_else(|_| "2".to_string());
+        let argon2_parallelism = std::env::var("ARGON2_PARALLELISM").unwrap_or_else(|_| "1".to_string());
+        let bcrypt_cost = std::env::var("BCRYPT_COST").unwrap_or_else(|_| "12".to_string());
+        // characters
+        let password_min_length = std::env::var("PASSWORD_MIN_LENGTH").unwrap_or_else(|_| "10".to_string());
+        // estimated bits, see utils::password_policy
+        let password_min_entropy = std::env::var("PASSWORD_MIN_ENTROPY").unwrap_or_else(|_| "40".to_string());
+        // one password per line, e.g. a top-N list of leaked passwords
+        let breached_passwords_file = std::env::var("BREACHED_PASSWORDS_FILE").ok();
         Config {
             database_url,
             jwt_secret,
-            jwt_expires_in,
+            jwt_algorithm,
+            jwt_signing_key_file,
+            jwt_signing_key_id,
+            jwt_verification_keys: jwt_verification_keys
+                .split(',')
+                .filter(|entry| !entry.trim().is_empty())
+                .map(|entry| {
+                    let (kid, path) = entry
+                        .split_once('=')
+                        .expect("JWT_VERIFICATION_KEYS entries must look like kid=path");
+                    (kid.trim().to_string(), path.trim().to_string())
+                })
+                .collect(),
+            jwt_expires_in: parse_duration(&jwt_expires_in)
+                .expect("JWT_EXPIRED_IN must be a number followed by s, m, h or d, e.g. 60m"),
             jwt_maxage: jwt_maxage.parse::<i32>().unwrap(),
+            cookie_secure: cookie_secure.parse::<bool>().unwrap(),
+            cookie_same_site: parse_same_site(&cookie_same_site)
+                .expect("COOKIE_SAME_SITE must be Strict, Lax or None"),
+            cookie_domain,
+            sliding_sessions: sliding_sessions.parse::<bool>().unwrap(),
+            sliding_session_threshold: sliding_session_threshold.parse::<i64>().unwrap(),
+            refresh_token_maxage: refresh_token_maxage.parse::<i64>().unwrap(),
+            app_url,
+            mailer,
+            mailer_file,
+            password_reset_ttl: password_reset_ttl.parse::<i64>().unwrap(),
+            email_verification_ttl: email_verification_ttl.parse::<i64>().unwrap(),
+            unverified_login,
+            login_max_attempts: login_max_attempts.parse::<u32>().unwrap(),
+            login_ip_max_attempts: login_ip_max_attempts.parse::<u32>().unwrap(),
+            login_backoff_seconds: login_backoff_seconds.parse::<u64>().unwrap(),
+            login_lockout_seconds: login_lockout_seconds.parse::<u64>().unwrap(),
+            totp_issuer,
+            password_hasher,
+            registration,
+            oidc_issuer,
+            oidc_client_id,
+            oidc_client_secret,
+            oidc_redirect_url,
+            oidc_scopes,
+            oidc_groups_claim,
+            oidc_role_mapping: oidc_role_mapping
+                .split(',')
+                .filter(|entry| !entry.trim().is_empty())
+                .map(|entry| {
+                    let (group, role) = entry
+                        .rsplit_once('=')
+                        .expect("OIDC_ROLE_MAPPING entries must look like group=role");
+                    (group.trim().to_string(), role.trim().to_string())
+                })
+                .collect(),
+            oidc_default_role,
+            oidc_post_login_redirect,
+            invitation_ttl: invitation_ttl.parse::<i64>().unwrap(),
+            argon2_memory_kib: argon2_memory_kib.parse::<u32>().unwrap(),
+            argon2_iterations: argon2_iterations.parse::<u32>().unwrap(),
+            argon2_parallelism: argon2_parallelism.parse::<u32>().unwrap(),
+            bcrypt_cost: bcrypt_cost.parse::<u32>().unwrap(),
+            password_min_length: password_min_length.parse::<usize>().unwrap(),
+            password_min_entropy: password_min_entropy.parse::<f64>().unwrap(),
+            breached_passwords_file,
         }
     }
 }
+
+// "90s", "60m", "12h", "7d"
+fn parse_duration(value: &str) -> Option<chrono::Duration> {
+    let value = value.trim();
+    let unit = value.chars().last()?;
+    let amount = value[..value.len() - unit.len_utf8()].parse::<i64>().ok()?;
+    match unit {
+        's' => Some(chrono::Duration::seconds(amount)),
+        'm' => Some(chrono::Duration::minutes(amount)),
+        'h' => Some(chrono::Duration::hours(amount)),
+        'd' => Some(chrono::Duration::days(amount)),
+        _ => None,
+    }
+}
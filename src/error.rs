@@ -1,5 +1,5 @@
 use axum::{http::StatusCode, response::IntoResponse, Json};
-use serde_json::json;
+use serde_json::{json, Value};
 
 #[derive(Debug)]
 pub enum AppError {
@@ -10,6 +10,9 @@ pub enum AppError {
     InternalServerError,
     UserDoesNotExist,
     UserAlreadyExits,
+    RegistrationClosed,
+    // field name -> what is wrong with it
+    InvalidFields(Value),
 }
 
 impl IntoResponse for AppError {
@@ -25,7 +28,15 @@ impl IntoResponse for AppError {
             Self::WrongCredential => (StatusCode::UNAUTHORIZED, "Wrong credentials"),
             Self::UserDoesNotExist => (StatusCode::UNAUTHORIZED, "User does not exist"),
             Self::UserAlreadyExits => (StatusCode::BAD_REQUEST, "User already exists"),
+            Self::RegistrationClosed => (StatusCode::FORBIDDEN, "Registration is by invitation only"),
+            Self::InvalidFields(fields) => return invalid_fields(fields).into_response(),
         };
         (status, Json(json!({ "error": err_msg }))).into_response()
     }
-}
\ No newline at end of file
+}
+
+// The one shape for field errors, also for handlers that don't return AppError
+pub fn invalid_fields(fields: Value) -> (StatusCode, Json<Value>) {
+    let body = json!({ "error": "Invalid fields", "fields": fields });
+    (StatusCode::UNPROCESSABLE_ENTITY, Json(body))
+}
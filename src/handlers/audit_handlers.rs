@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json
+};
+
+use serde_json::json;
+use sqlx::{MySql, QueryBuilder};
+use crate::{
+    handlers::auth_handlers::db_error,
+    model::AuthEventModel,
+    schema::AuditFilterOptions,
+    AppState
+};
+
+// Audit Handlers -------------------------------------------
+
+// Newest events first
+pub async fn audit_list_handler(
+    opts: Option<Query<AuditFilterOptions>>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let Query(opts) = opts.unwrap_or_default();
+
+    let limit = opts.limit.unwrap_or(50).clamp(1, 500);
+    let page = opts.page.unwrap_or(1).max(1);
+    let offset = (page - 1) * limit;
+
+    let mut count_query = QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM auth_events");
+    push_audit_filters(&mut count_query, &opts);
+    let total: i64 = count_query
+        .build_query_scalar()
+        .fetch_one(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let mut query = QueryBuilder::<MySql>::new("SELECT * FROM auth_events");
+    push_audit_filters(&mut query, &opts);
+    query.push(" ORDER BY created_at DESC, id DESC LIMIT ");
+    query.push_bind(limit as i64);
+    query.push(" OFFSET ");
+    query.push_bind(offset as i64);
+    let events = query
+        .build_query_as::<AuthEventModel>()
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "page": page,
+        "limit": limit,
+        "total": total,
+        "events": events,
+    })))
+}
+
+fn push_audit_filters(query: &mut QueryBuilder<'_, MySql>, opts: &AuditFilterOptions) {
+    query.push(" WHERE 1 = 1");
+    if let Some(user_id) = opts.user_id {
+        query.push(" AND user_id = ");
+        query.push_bind(user_id);
+    }
+    if let Some(event) = &opts.event {
+        query.push(" AND event = ");
+        query.push_bind(event.clone());
+    }
+    if let Some(ip) = &opts.ip {
+        query.push(" AND ip = ");
+        query.push_bind(ip.clone());
+    }
+    if let Some(from) = opts.from {
+        query.push(" AND created_at >= ");
+        query.push_bind(from);
+    }
+    if let Some(to) = opts.to {
+        query.push(" AND created_at < ");
+        query.push_bind(to);
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use chrono::prelude::*;
 use axum::{
     extract::State,
@@ -7,36 +7,148 @@ use axum::{
     response::IntoResponse, 
     Extension, Json
 };
-use axum_extra::extract::cookie::{Cookie, SameSite};
-use jsonwebtoken::{ EncodingKey, Header};
-use bcrypt::{DEFAULT_COST, hash, verify};
+use axum_extra::extract::cookie::CookieJar;
 
 use serde_json::{json, Value};
 use sqlx::MySqlPool;
 use crate::{
+    config::Config,
     error::AppError, 
-    model::{FilteredUser, LoginModel, RegisterModel}, 
-    schema::{FilterOptions, LoginSchema, RegisterSchema}, 
-    utils::jwt::token_encode, AppState
+    model::{EmailVerificationModel, FilteredUser, InvitationModel, LoginModel, PasswordResetModel, RegisterModel}, 
+    schema::{FilterOptions, ForgotPasswordSchema, LoginSchema, MfaLoginSchema, RegisterSchema, ResendVerificationSchema, ResetPasswordSchema, VerifyEmailSchema}, 
+    utils::{
+        cookies::{access_cookie, csrf_cookie, refresh_cookie, removal_cookies, REFRESH_COOKIE},
+        csrf::{generate_csrf_token, CSRF_COOKIE},
+        guard::extract_token,
+        audit::{AuditEvent, AuditRecord, ClientInfo},
+        mailer::{send_email, Email},
+        password::{hash_password, verify_password},
+        jwt::{mfa_token_decode, mfa_token_encode, token_decode, token_encode},
+        refresh::{find_refresh_token, issue_refresh_token, revoke_refresh_token},
+        revocation::{revoke_access_token, revoke_all_sessions},
+        session::{create_session, extend_session, find_session, revoke_session},
+        token::{generate_token, hash_token},
+        totp::{consume_recovery_code, consume_totp_code},
+    },
+    AppState
 };
+
 // Auth Handlers -------------------------------------------
 
+// Exchanges the refresh token cookie for a new access token. The refresh token
+// is rotated on every call; presenting one that was already rotated means it
+// leaked, so the whole family is revoked and the user has to log in again.
 pub async fn refresh_token_handler(
-    headers: HeaderMap,
+    cookie_jar: CookieJar,
+    client: ClientInfo,
     State(data): State<Arc<AppState>>,
-    mut req: Request<Body>,
  ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-        let token = req.headers()
-                .get(header::AUTHORIZATION)
-                .and_then(|auth_header| auth_header.to_str().ok())
-                .and_then(|auth_value| {
-                    if auth_value.starts_with("Bearer ") {
-                        Some(auth_value[7..].to_owned())
-                    } else {
-                        None
-                    }
-                });
-    Ok(Json(json!({"access_token": token})))
+    let token = cookie_jar
+        .get(REFRESH_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .ok_or_else(|| {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "Missing refresh token",
+            });
+            (StatusCode::UNAUTHORIZED, Json(error_response))
+        })?;
+
+    let record = find_refresh_token(&data.db, &token)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "Invalid refresh token",
+            });
+            (StatusCode::UNAUTHORIZED, Json(error_response))
+        })?;
+
+    // a revoked session's tokens are revoked too, that isn't reuse
+    let session = find_session(&data.db, &record.family_id).await.map_err(db_error)?;
+    if matches!(&session, Some(session) if session.revoked_at.is_some()) {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Session has been revoked, please log in again",
+        });
+        return Err((StatusCode::UNAUTHORIZED, Json(error_response)));
+    }
+
+    // a rotated token that shows up again, or two concurrent refreshes with the same token
+    let rotated = record.revoked_at.is_none()
+        && revoke_refresh_token(&data.db, record.id).await.map_err(db_error)?;
+    if !rotated {
+        revoke_session(&data.db, &record.family_id)
+            .await
+            .map_err(db_error)?;
+        AuditRecord::new(AuditEvent::RefreshReuse, &client)
+            .user(record.user_id)
+            .record(&data.db)
+            .await;
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Refresh token reuse detected, please log in again",
+        });
+        return Err((StatusCode::UNAUTHORIZED, Json(error_response)));
+    }
+
+    if record.expires_at < Utc::now() {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Refresh token has expired, please log in again",
+        });
+        return Err((StatusCode::UNAUTHORIZED, Json(error_response)));
+    }
+
+    let user = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE id = ?"#)
+        .bind(record.user_id)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "The user belonging to this token no longer exists",
+            });
+            (StatusCode::UNAUTHORIZED, Json(error_response))
+        })?;
+    if let Some(error_response) = login_blocked(&user) {
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+
+    let refresh_token = issue_refresh_token(
+        &data.db,
+        user.id,
+        &record.family_id,
+        data.env.refresh_token_maxage,
+    )
+    .await
+    .map_err(db_error)?;
+    // sessions started before they were tracked get a row now
+    match session {
+        Some(_) => extend_session(&data.db, &record.family_id, refresh_expiry(&data.env)).await,
+        None => create_session(&data.db, &record.family_id, user.id, &client, refresh_expiry(&data.env)).await,
+    }
+    .map_err(db_error)?;
+    let token = token_encode(
+        &data.keys,
+        user.id.to_string(),
+        user.token_version,
+        record.family_id.clone(),
+        data.env.jwt_expires_in,
+    );
+    AuditRecord::new(AuditEvent::Refresh, &client)
+        .user(user.id)
+        .record(&data.db)
+        .await;
+
+    Ok(session_response(
+        &data.env,
+        json!({"status": "success", "token": token}),
+        token,
+        refresh_token,
+    ))
 }
 
 pub async fn get_me_handler(
@@ -52,19 +164,100 @@ pub async fn get_me_handler(
     Ok(Json(json_response))
 }
 
-pub async fn logout_handler() -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    // clear token to logout
-    let cookie = Cookie::build(("token", ""))
-        .path("/")
-        .max_age(time::Duration::hours(-1))
-        .same_site(SameSite::Lax)
-        .http_only(true);
+// Hands out a CSRF token for cookie-authenticated clients that lost theirs
+pub async fn csrf_token_handler(
+    cookie_jar: CookieJar,
+    State(data): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let csrf_token = match cookie_jar.get(CSRF_COOKIE) {
+        Some(cookie) if !cookie.value().is_empty() => cookie.value().to_string(),
+        _ => generate_csrf_token(),
+    };
+    let cookie = csrf_cookie(&data.env, csrf_token.clone());
 
-    let mut response = Response::new(json!({"status": "success", "message": "successfully logged out"}).to_string());
+    let mut response = Response::new(json!({"status": "success", "csrf_token": csrf_token}).to_string());
     response
         .headers_mut()
-        .insert(header::SET_COOKIE, cookie.to_string().parse().unwrap());
-    Ok(response)
+        .append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+    response
+}
+
+// Public keys the access tokens can be verified with (empty with HS256)
+pub async fn jwks_handler(
+    State(data): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    Json(data.keys.jwks().clone())
+}
+
+pub async fn logout_handler(
+    cookie_jar: CookieJar,
+    headers: HeaderMap,
+    client: ClientInfo,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut user_id = None;
+    // a copy of the access token (e.g. from the login response) must stop working too
+    if let Some(token) = extract_token(&cookie_jar, &headers) {
+        if let Ok(claims) = token_decode(token, &data.keys) {
+            revoke_access_token(&data.db, &claims)
+                .await
+                .map_err(db_error)?;
+            if let Some(sid) = &claims.sid {
+                revoke_session(&data.db, sid).await.map_err(db_error)?;
+            }
+            user_id = claims.sub.parse::<i64>().ok();
+        }
+    }
+
+    // the refresh token would otherwise keep minting access tokens
+    if let Some(refresh_cookie) = cookie_jar.get(REFRESH_COOKIE) {
+        if let Some(record) = find_refresh_token(&data.db, refresh_cookie.value())
+            .await
+            .map_err(db_error)?
+        {
+            revoke_session(&data.db, &record.family_id)
+                .await
+                .map_err(db_error)?;
+            user_id = Some(record.user_id);
+        }
+    }
+
+    // nothing to audit when the caller wasn't logged in
+    if let Some(user_id) = user_id {
+        AuditRecord::new(AuditEvent::Logout, &client)
+            .user(user_id)
+            .record(&data.db)
+            .await;
+    }
+    Ok(logout_response(&data.env, "successfully logged out"))
+}
+
+// Invalidates every access and refresh token of the user, on all devices
+pub async fn logout_all_handler(
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    revoke_all_sessions(&data.db, user.id)
+        .await
+        .map_err(db_error)?;
+    AuditRecord::new(AuditEvent::LogoutAll, &client)
+        .user(user.id)
+        .record(&data.db)
+        .await;
+
+    Ok(logout_response(&data.env, "successfully logged out of all sessions"))
+}
+
+fn logout_response(config: &Config, message: &str) -> Response<String> {
+    // clear token to logout
+    let mut response = Response::new(json!({"status": "success", "message": message}).to_string());
+    for cookie in removal_cookies(config) {
+        response
+            .headers_mut()
+            .append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+    }
+    response
 }
 
 pub async fn register_handler(
@@ -86,29 +279,364 @@ pub async fn register_handler(
     if let Some(_) = result{
         return Err(AppError::UserAlreadyExits);
     }
-    let hashed_password = match hash(req.password.clone().to_string(), DEFAULT_COST){
-        Ok(h) => h,
-        Err(e) => panic!("Error hashing {:}", e),
+    if let Some(fields) = data.password_policy.field_errors("password", &req.password, &req.email, &req.name) {
+        return Err(AppError::InvalidFields(fields));
+    }
+    let invitation = match &req.invitation {
+        Some(token) => Some(find_invitation(&data.db, token, &req.email).await?),
+        None if data.env.registration == "invite" => return Err(AppError::RegistrationClosed),
+        None => None,
     };
-
-    let create_user = sqlx::query("INSERT INTO login (name, email, password) VALUES (?, ?, ?)")
-        .bind(req.name.to_string())
-        .bind(req.email.to_string())
-        .bind(&hashed_password)
-        .execute(&data.db)
+    let hashed_password = hash_password(data.password_hasher.clone(), req.password.clone())
         .await
         .map_err(|_| AppError::InternalServerError)?;
+
+    let mut tx = data.db.begin().await.map_err(|_| AppError::InternalServerError)?;
+    let create_user = match &invitation {
+        // the invitation was emailed to this address, so it counts as verified
+        Some(invitation) => {
+            let claimed = sqlx::query(
+                r#"UPDATE invitations SET accepted_at = ? WHERE id = ? AND accepted_at IS NULL AND revoked_at IS NULL"#,
+            )
+            .bind(Utc::now())
+            .bind(invitation.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| AppError::InternalServerError)?;
+            if claimed.rows_affected() < 1 {
+                return Err(AppError::InvalidToken);
+            }
+            sqlx::query("INSERT INTO login (name, email, password, role, email_verified_at) VALUES (?, ?, ?, ?, ?)")
+                .bind(req.name.to_string())
+                .bind(req.email.to_string())
+                .bind(&hashed_password)
+                .bind(&invitation.role)
+                .bind(Utc::now())
+                .execute(&mut *tx)
+                .await
+        }
+        None => sqlx::query("INSERT INTO login (name, email, password) VALUES (?, ?, ?)")
+            .bind(req.name.to_string())
+            .bind(req.email.to_string())
+            .bind(&hashed_password)
+            .execute(&mut *tx)
+            .await,
+    }
+    .map_err(|_| AppError::InternalServerError)?;
     if create_user.rows_affected() < 1 {
-        Err(AppError::InternalServerError)
+        return Err(AppError::InternalServerError);
+    }
+    tx.commit().await.map_err(|_| AppError::InternalServerError)?;
+
+    if invitation.is_some() {
+        return Ok(Json(json!({ "status": "success", "result": "User successfully registered, you can now log in" })));
+    }
+    send_verification_email(&data, create_user.last_insert_id() as i64, &req.email, false).await?;
+    Ok(Json(json!({ "status": "success", "result": "User successfully registered, check your email to verify your account" })))
+}
+
+// A pending invitation for `email`
+async fn find_invitation(db: &MySqlPool, token: &str, email: &str) -> Result<InvitationModel, AppError> {
+    let invitation = sqlx::query_as::<_, InvitationModel>(
+        r#"SELECT * FROM invitations WHERE token_hash = ? AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > ?"#,
+    )
+    .bind(hash_token(token))
+    .bind(Utc::now())
+    .fetch_optional(db)
+    .await
+    .map_err(|_| AppError::InternalServerError)?
+    .ok_or(AppError::InvalidToken)?;
+
+    if !invitation.email.eq_ignore_ascii_case(email.trim()) {
+        return Err(AppError::InvalidToken);
+    }
+    Ok(invitation)
+}
+
+pub async fn verify_email_handler(
+    State(data): State<Arc<AppState>>,
+    Json(req): Json<VerifyEmailSchema>,
+) -> Result<Json<Value>, AppError> {
+    if req.token.is_empty() {
+        return Err(AppError::MissingCredential);
+    }
+    let verification = sqlx::query_as::<_, EmailVerificationModel>(
+        r#"SELECT * FROM email_verifications WHERE token_hash = ? AND used_at IS NULL AND expires_at > ?"#,
+    )
+    .bind(hash_token(&req.token))
+    .bind(Utc::now())
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|_| AppError::InternalServerError)?
+    .ok_or(AppError::InvalidToken)?;
+
+    // one transaction: a rejected email change leaves the link usable
+    let mut tx = data.db.begin().await.map_err(|_| AppError::InternalServerError)?;
+    let claimed = sqlx::query(r#"UPDATE email_verifications SET used_at = ? WHERE id = ? AND used_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(verification.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+    if claimed.rows_affected() < 1 {
+        return Err(AppError::InvalidToken);
+    }
+
+    // an email change: the new address replaces the old one now that it is verified
+    let result = if let Some(new_email) = &verification.email {
+        let taken = sqlx::query_as::<_, RegisterModel>(
+            r#"SELECT email, password, name FROM login WHERE email = ? AND id <> ?"#,
+        )
+        .bind(new_email)
+        .bind(verification.user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+        if taken.is_some() {
+            return Err(AppError::UserAlreadyExits);
+        }
+        // another account can still take the address between the check and here
+        sqlx::query(r#"UPDATE login SET email = ?, email_verified_at = ? WHERE id = ?"#)
+            .bind(new_email)
+            .bind(Utc::now())
+            .bind(verification.user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(e) if e.is_unique_violation() => AppError::UserAlreadyExits,
+                _ => AppError::InternalServerError,
+            })?;
+        "Email successfully changed"
     } else {
-        Ok(Json(json!({ "status": "success", "result": "User successfully registered" })))
+        sqlx::query(r#"UPDATE login SET email_verified_at = ? WHERE id = ?"#)
+            .bind(Utc::now())
+            .bind(verification.user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| AppError::InternalServerError)?;
+        "Email successfully verified"
+    };
+    tx.commit().await.map_err(|_| AppError::InternalServerError)?;
+
+    Ok(Json(json!({ "status": "success", "result": result })))
+}
+
+// Like forgot_password_handler, the answer doesn't depend on whether the account exists
+pub async fn resend_verification_handler(
+    State(data): State<Arc<AppState>>,
+    Json(req): Json<ResendVerificationSchema>,
+) -> Result<Json<Value>, AppError> {
+    if req.email.is_empty() {
+        return Err(AppError::MissingCredential);
+    }
+    let user = sqlx::query_as::<_, LoginModel>(
+        r#"SELECT * FROM login WHERE email = ? AND email_verified_at IS NULL"#,
+    )
+    .bind(req.email.to_ascii_lowercase())
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|_| AppError::InternalServerError)?;
+
+    if let Some(user) = user {
+        send_verification_email(&data, user.id, &user.email.clone().unwrap_or_default(), false).await?;
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "If an unverified account exists for that email, a verification link has been sent"
+    })))
+}
+
+// Replaces any pending verification token of the user and emails a new one.
+// With `email_change` the address is remembered on the token and becomes the
+// account's email once verified.
+pub(crate) async fn send_verification_email(
+    data: &AppState,
+    user_id: i64,
+    email: &str,
+    email_change: bool,
+) -> Result<(), AppError> {
+    // only older links of the same kind: a pending email change and a signup
+    // verification don't cancel each other
+    sqlx::query(
+        r#"UPDATE email_verifications SET used_at = ? WHERE user_id = ? AND used_at IS NULL AND (email IS NOT NULL) = ?"#,
+    )
+    .bind(Utc::now())
+    .bind(user_id)
+    .bind(email_change)
+    .execute(&data.db)
+    .await
+    .map_err(|_| AppError::InternalServerError)?;
+
+    let token = generate_token();
+    sqlx::query(r#"INSERT INTO email_verifications (user_id, token_hash, expires_at, email) VALUES (?, ?, ?, ?)"#)
+        .bind(user_id)
+        .bind(hash_token(&token))
+        .bind(Utc::now() + chrono::Duration::hours(data.env.email_verification_ttl))
+        .bind(if email_change { Some(email) } else { None })
+        .execute(&data.db)
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+
+    let email = Email {
+        to: email.to_string(),
+        subject: "Verify your email address".to_string(),
+        body: format!(
+            "Use the link below to verify your email address. It expires in {} hours.\n\n{}/verify-email?token={}",
+            data.env.email_verification_ttl, data.env.app_url, token
+        ),
+    };
+    if let Err(e) = send_email(data.mailer.clone(), email).await {
+        println!("🔥 {}", e);
     }
+    Ok(())
+}
+
+// Always answers with the same message so the endpoint can't be used to find
+// out which emails have an account.
+pub async fn forgot_password_handler(
+    State(data): State<Arc<AppState>>,
+    Json(req): Json<ForgotPasswordSchema>,
+) -> Result<Json<Value>, AppError> {
+    if req.email.is_empty() {
+        return Err(AppError::MissingCredential);
+    }
+    let user = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE email = ?"#)
+        .bind(req.email.to_ascii_lowercase())
+        .fetch_optional(&data.db)
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+
+    if let Some(user) = user {
+        send_password_reset(&data, &user)
+            .await
+            .map_err(|_| AppError::InternalServerError)?;
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "If an account exists for that email, a password reset link has been sent"
+    })))
+}
+
+// Emails a single-use password reset link to the user. Also used by admins to
+// force a reset.
+pub(crate) async fn send_password_reset(data: &AppState, user: &LoginModel) -> Result<(), sqlx::Error> {
+    // only the most recent link works
+    sqlx::query(r#"UPDATE password_resets SET used_at = ? WHERE user_id = ? AND used_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(user.id)
+        .execute(&data.db)
+        .await?;
+
+    let token = generate_token();
+    sqlx::query(r#"INSERT INTO password_resets (user_id, token_hash, expires_at) VALUES (?, ?, ?)"#)
+        .bind(user.id)
+        .bind(hash_token(&token))
+        .bind(Utc::now() + chrono::Duration::minutes(data.env.password_reset_ttl))
+        .execute(&data.db)
+        .await?;
+
+    let email = Email {
+        to: user.email.clone().unwrap_or_default(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Use the link below to choose a new password. It expires in {} minutes.\n\n{}/reset-password?token={}",
+            data.env.password_reset_ttl, data.env.app_url, token
+        ),
+    };
+    if let Err(e) = send_email(data.mailer.clone(), email).await {
+        println!("🔥 {}", e);
+    }
+    Ok(())
+}
+
+pub async fn reset_password_handler(
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(req): Json<ResetPasswordSchema>,
+) -> Result<Json<Value>, AppError> {
+    if req.token.is_empty() || req.password.is_empty() {
+        return Err(AppError::MissingCredential);
+    }
+    let reset = sqlx::query_as::<_, PasswordResetModel>(
+        r#"SELECT * FROM password_resets WHERE token_hash = ? AND used_at IS NULL AND expires_at > ?"#,
+    )
+    .bind(hash_token(&req.token))
+    .bind(Utc::now())
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|_| AppError::InternalServerError)?
+    .ok_or(AppError::InvalidToken)?;
+
+    // checked before the token is burned, so the user can try another password
+    let user = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE id = ?"#)
+        .bind(reset.user_id)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(|_| AppError::InternalServerError)?
+        .ok_or(AppError::InvalidToken)?;
+    if let Some(fields) = data.password_policy.field_errors(
+        "password",
+        &req.password,
+        user.email.as_deref().unwrap_or_default(),
+        user.name.as_deref().unwrap_or_default(),
+    ) {
+        return Err(AppError::InvalidFields(fields));
+    }
+
+    // burn the token first so two concurrent requests can't both use it
+    let claimed = sqlx::query(r#"UPDATE password_resets SET used_at = ? WHERE id = ? AND used_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(reset.id)
+        .execute(&data.db)
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+    if claimed.rows_affected() < 1 {
+        return Err(AppError::InvalidToken);
+    }
+
+    let hashed_password = hash_password(data.password_hasher.clone(), req.password.clone())
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+    sqlx::query(r#"UPDATE login SET password = ?, password_reset_required = FALSE WHERE id = ?"#)
+        .bind(&hashed_password)
+        .bind(reset.user_id)
+        .execute(&data.db)
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+
+    // whoever knew the old password shouldn't stay logged in
+    revoke_all_sessions(&data.db, reset.user_id)
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+    AuditRecord::new(AuditEvent::PasswordReset, &client)
+        .user(reset.user_id)
+        .record(&data.db)
+        .await;
+
+    Ok(Json(json!({ "status": "success", "result": "Password successfully reset" })))
 }
 
 pub async fn login_handler(
     State(data): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(req): Json<LoginSchema>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let ip = client.ip.clone();
+    if let Err(wait) = data.login_throttle.check(&req.email, &ip) {
+        AuditRecord::new(AuditEvent::LoginFailure, &client)
+            .email(&req.email)
+            .detail("throttled")
+            .record(&data.db)
+            .await;
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": format!("Too many failed login attempts, try again in {} seconds", wait.as_secs().max(1)),
+            "retry_after": wait.as_secs().max(1),
+        });
+        return Err((StatusCode::TOO_MANY_REQUESTS, Json(error_response)));
+    }
 
     let result = sqlx::query_as!(
         LoginModel,
@@ -123,51 +651,292 @@ pub async fn login_handler(
                 "message": format!("Database error: {}", e),
         });
         (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-    })?
-    .ok_or_else(|| {
-        let error_response = serde_json::json!({
-            "status": "error",
-            "message": "Invalid email",
-        });
-        (StatusCode::BAD_REQUEST, Json(error_response))
     })?;
-    // compare request password with hashed password in db and return true or false
-    let is_valid = match verify(
-        &req.password.as_bytes(),
-        &result.password.clone().unwrap()){
-        Ok(true) => true,
-        Ok(false) => false,
-        Err(err) => false,
+    // compare request password with hashed password in db and return true or false.
+    // Unknown emails are checked against a dummy hash so they take just as long.
+    let password_hash = match result.as_ref().and_then(|user| user.password.clone()) {
+        Some(password_hash) => password_hash,
+        None => dummy_password_hash(&data).await,
+    };
+    let is_valid = verify_password(data.password_hasher.clone(), req.password.clone(), password_hash.clone()).await;
+
+    let result = match result {
+        Some(result) if is_valid => result,
+        _ => {
+            data.login_throttle.record_failure(&req.email, &ip);
+            let mut audit = AuditRecord::new(AuditEvent::LoginFailure, &client)
+                .email(&req.email)
+                .detail("invalid credentials");
+            if let Some(user) = &result {
+                audit = audit.user(user.id);
+            }
+            audit.record(&data.db).await;
+            // same answer for a wrong email and a wrong password
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "Invalid email or password"
+            });
+            return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+        }
     };
 
+    // in "limited" mode require_verified keeps the account away from everything else
+    if result.email_verified_at.is_none() && data.env.unverified_login != "limited" {
+        AuditRecord::new(AuditEvent::LoginFailure, &client)
+            .user(result.id)
+            .email(&req.email)
+            .detail("email not verified")
+            .record(&data.db)
+            .await;
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Please verify your email address before logging in"
+        });
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+
+    if let Some(error_response) = login_blocked(&result) {
+        AuditRecord::new(AuditEvent::LoginFailure, &client)
+            .user(result.id)
+            .email(&req.email)
+            .detail(error_response["message"].as_str().unwrap_or_default())
+            .record(&data.db)
+            .await;
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+    rehash_if_outdated(&data, result.id, &req.password, &password_hash).await;
+
+    // enrolled users exchange this token together with a TOTP code at /api/login/2fa.
+    // Failed attempts are only cleared once the second step succeeds.
+    if result.totp_enabled_at.is_some() {
+        let mfa_token = mfa_token_encode(result.id.to_string(), &data.keys);
+        let json_response = serde_json::json!({
+            "status": "mfa_required",
+            "mfa_token": mfa_token,
+        });
+        return Ok(Json(json_response).into_response());
+    }
+    data.login_throttle.record_success(&req.email);
+
+    let response = start_session(&data, &result, &client).await?;
+    AuditRecord::new(AuditEvent::LoginSuccess, &client)
+        .user(result.id)
+        .email(&req.email)
+        .record(&data.db)
+        .await;
+    Ok(response.into_response())
+}
+
+// Second step of a 2FA login
+pub async fn mfa_login_handler(
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(req): Json<MfaLoginSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let claims = mfa_token_decode(&req.mfa_token, &data.keys)
+        .map_err(|(status, Json(e))| {
+            (status, Json(json!({"status": "fail", "message": e.message})))
+        })?;
+
+    let user = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE id = ?"#)
+        .bind(claims.sub.parse::<i64>().unwrap_or_default())
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "The user belonging to this token no longer exists",
+            });
+            (StatusCode::UNAUTHORIZED, Json(error_response))
+        })?;
+    if let Some(error_response) = login_blocked(&user) {
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+
+    // the same throttle as the password step, six digits are easy to guess otherwise
+    let email = user.email.clone().unwrap_or_default();
+    let ip = client.ip.clone();
+    if let Err(wait) = data.login_throttle.check(&email, &ip) {
+        AuditRecord::new(AuditEvent::LoginFailure, &client)
+            .user(user.id)
+            .email(&email)
+            .detail("throttled")
+            .record(&data.db)
+            .await;
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": format!("Too many failed login attempts, try again in {} seconds", wait.as_secs().max(1)),
+            "retry_after": wait.as_secs().max(1),
+        });
+        return Err((StatusCode::TOO_MANY_REQUESTS, Json(error_response)));
+    }
+
+    let is_valid = match (&req.code, &req.recovery_code) {
+        (Some(code), _) => consume_totp_code(&data.db, &user, code).await.map_err(db_error)?,
+        (None, Some(recovery_code)) => consume_recovery_code(&data.db, user.id, recovery_code)
+            .await
+            .map_err(db_error)?,
+        (None, None) => false,
+    };
     if !is_valid {
+        data.login_throttle.record_failure(&email, &ip);
+        AuditRecord::new(AuditEvent::LoginFailure, &client)
+            .user(user.id)
+            .email(&email)
+            .detail("invalid two-factor code")
+            .record(&data.db)
+            .await;
         let error_response = serde_json::json!({
             "status": "fail",
-            "message": "Invalid password"
+            "message": "Invalid two-factor code"
         });
         return Err((StatusCode::BAD_REQUEST, Json(error_response)));
     }
+    data.login_throttle.record_success(&email);
+
+    let response = start_session(&data, &user, &client).await?;
+    AuditRecord::new(AuditEvent::LoginSuccess, &client)
+        .user(user.id)
+        .email(&email)
+        .detail("two-factor")
+        .record(&data.db)
+        .await;
+    Ok(response)
+}
+
+// Accounts an admin deactivated or forced to reset their password can't log in,
+// even with the right password
+pub(crate) fn login_blocked(user: &LoginModel) -> Option<Value> {
+    let message = if user.deactivated_at.is_some() {
+        "This account has been deactivated"
+    } else if user.password_reset_required {
+        "A password reset is required, use the link sent to your email"
+    } else {
+        return None;
+    };
+    Some(json!({"status": "fail", "message": message}))
+}
+
+// Issues the access and refresh tokens for a user who has proven who they are,
+// under a new session that shows up in /api/sessions
+pub(crate) async fn start_session(
+    data: &AppState,
+    user: &LoginModel,
+    client: &ClientInfo,
+) -> Result<Response<String>, (StatusCode, Json<serde_json::Value>)> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    create_session(&data.db, &session_id, user.id, client, refresh_expiry(&data.env))
+        .await
+        .map_err(db_error)?;
+    let refresh_token = issue_refresh_token(
+        &data.db,
+        user.id,
+        &session_id,
+        data.env.refresh_token_maxage,
+    )
+    .await
+    .map_err(db_error)?;
+    let token = token_encode(
+        &data.keys,
+        user.id.to_string(),
+        user.token_version,
+        session_id,
+        data.env.jwt_expires_in,
+    );
 
-    let token = token_encode(&Header::default(), result.id.clone().to_string(), &EncodingKey::from_secret(data.env.jwt_secret.as_ref()));
+    Ok(session_response(
+        &data.env,
+        json!({"status": "success", "token": token, "user": filter_user_record(user)}),
+        token,
+        refresh_token,
+    ))
+}
 
-    let cookie = Cookie::build(("token", token.to_owned()))
-        .path("/")
-        .max_age(time::Duration::hours(1))
-        .same_site(SameSite::Lax)
-        .http_only(true);
+// Builds the response for a freshly started session: the access token goes in
+// the `token` cookie, the refresh token in an httpOnly cookie scoped to /api,
+// and a new CSRF token in both the `csrf_token` cookie and the body.
+fn session_response(
+    config: &Config,
+    mut body: Value,
+    access_token: String,
+    refresh_token: String,
+) -> Response<String> {
+    let csrf_token = generate_csrf_token();
+    body["csrf_token"] = json!(csrf_token);
+    let cookies = [
+        access_cookie(config, access_token),
+        refresh_cookie(config, refresh_token),
+        csrf_cookie(config, csrf_token),
+    ];
 
-    let mut response = Response::new(json!({"status": "success", "token": token, "user": filter_user_record(&result)}).to_string());
+    let mut response = Response::new(body.to_string());
+    for cookie in cookies {
+        response
+            .headers_mut()
+            .append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+    }
     response
-        .headers_mut()
-        .insert(header::SET_COOKIE, cookie.to_string().parse().unwrap());
-    Ok(response)
 }
 
-fn filter_user_record(result: &LoginModel) -> FilteredUser {
+// Matches the expiry issue_refresh_token gives a new refresh token
+fn refresh_expiry(config: &Config) -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::days(config.refresh_token_maxage)
+}
+
+async fn dummy_password_hash(data: &AppState) -> String {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    if let Some(dummy_hash) = DUMMY_HASH.get() {
+        return dummy_hash.clone();
+    }
+    let dummy_hash = hash_password(data.password_hasher.clone(), "dummy password".to_string())
+        .await
+        .unwrap_or_default();
+    DUMMY_HASH.get_or_init(|| dummy_hash).clone()
+}
+
+// Upgrades a hash made with an older algorithm or weaker parameters now that we
+// have the password in clear text. A failure only means trying again next login.
+async fn rehash_if_outdated(data: &AppState, user_id: i64, password: &str, password_hash: &str) {
+    if !data.password_hasher.needs_rehash(password_hash) {
+        return;
+    }
+    let new_hash = match hash_password(data.password_hasher.clone(), password.to_string()).await {
+        Ok(new_hash) => new_hash,
+        Err(e) => {
+            println!("🔥 {}", e);
+            return;
+        }
+    };
+    // skipped if the password changed in the meantime
+    let result = sqlx::query(r#"UPDATE login SET password = ? WHERE id = ? AND password = ?"#)
+        .bind(new_hash)
+        .bind(user_id)
+        .bind(password_hash)
+        .execute(&data.db)
+        .await;
+    if let Err(e) = result {
+        println!("🔥 Failed to rehash password: {}", e);
+    }
+}
+
+pub(crate) fn db_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = serde_json::json!({
+        "status": "error",
+        "message": format!("Database error: {}", e),
+    });
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+}
+
+// Also mapped over whole pages of users, so a NULL column comes out empty
+// instead of panicking
+pub(crate) fn filter_user_record(result: &LoginModel) -> FilteredUser {
     FilteredUser {
         id: result.id.to_string(),
-        email: result.email.clone().unwrap(),
-        name: result.name.clone().unwrap(),
-        role: result.role.clone().unwrap(),
+        email: result.email.clone().unwrap_or_default(),
+        name: result.name.clone().unwrap_or_default(),
+        role: result.role.clone().unwrap_or_default(),
+        email_verified: result.email_verified_at.is_some(),
+        active: result.deactivated_at.is_none(),
     }
 }
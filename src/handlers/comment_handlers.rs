@@ -19,35 +19,50 @@ use crate::{
 
 pub async fn create_comment_handler(
     State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
     Json(body): Json<CreateCommentSchema>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let ticket = sqlx::query(r#"SELECT id FROM tickets WHERE id = ?"#)
+        .bind(body.ticket_id)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"status": "error","message": format!("{:?}", e)})),
+            )
+        })?;
+    if ticket.is_none() {
+        return Err(ticket_not_found(body.ticket_id));
+    }
+
     let query_result =
         sqlx::query(r#"INSERT INTO comments (content, ticket_id, author_id) VALUES (?, ?, ?)"#)
             .bind(body.content.to_string())
             .bind(body.ticket_id.to_owned())
-            .bind(body.author_id.to_owned())
+            .bind(user.id)
             .execute(&data.db)
-            .await
-            .map_err(|err: sqlx::Error| err.to_string());
+            .await;
 
-    if let Err(err) = query_result {
-        if err.contains("Duplicate entry") {
-            let error_response = serde_json::json!({
-                "status": "error",
-                "message": "Ticket with that title already exists",
-            });
-            return Err((StatusCode::CONFLICT, Json(error_response)));
+    match query_result {
+        Ok(_) => Ok(Json(serde_json::json!({"status": "success"}))),
+        // the ticket was deleted between the lookup and the insert
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+            Err(ticket_not_found(body.ticket_id))
         }
-
-        return Err((
+        Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"status": "error","message": format!("{:?}", err)})),
-        ));
-    }      
-    let response_status = serde_json::json!({"status": "success"});
-    Ok(Json(response_status))
-    
+            Json(json!({"status": "error","message": format!("{:?}", e)})),
+        )),
+    }
+}
 
+fn ticket_not_found(ticket_id: i64) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = serde_json::json!({
+        "status": "fail",
+        "message": format!("Ticket with ID: {} not found", ticket_id)
+    });
+    (StatusCode::NOT_FOUND, Json(error_response))
 }
 
 pub async fn comments_list_handler(
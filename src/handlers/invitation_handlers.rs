@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use chrono::prelude::*;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json
+};
+
+use serde_json::json;
+use crate::{
+    handlers::auth_handlers::db_error,
+    model::{InvitationModel, LoginModel},
+    schema::CreateInvitationSchema,
+    utils::{mailer::{send_email, Email}, token::{generate_token, hash_token}},
+    AppState
+};
+
+// Invitation Handlers --------------------------------------
+
+// Pending invitations, newest first
+pub async fn invitation_list_handler(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let invitations = sqlx::query_as::<_, InvitationModel>(
+        r#"SELECT * FROM invitations WHERE accepted_at IS NULL AND revoked_at IS NULL AND expires_at > ? ORDER BY created_at DESC"#,
+    )
+    .bind(Utc::now())
+    .fetch_all(&data.db)
+    .await
+    .map_err(db_error)?;
+
+    Ok(Json(json!(invitations)))
+}
+
+// Emails a registration link. Earlier pending invitations for the same email stop working.
+pub async fn create_invitation_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(admin): Extension<LoginModel>,
+    Json(body): Json<CreateInvitationSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let email = body.email.trim().to_ascii_lowercase();
+    if !email.contains('@') {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Invalid email address",
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    let existing = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE email = ?"#)
+        .bind(&email)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?;
+    if existing.is_some() {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "An account with that email already exists",
+        });
+        return Err((StatusCode::CONFLICT, Json(error_response)));
+    }
+
+    sqlx::query(r#"UPDATE invitations SET revoked_at = ? WHERE email = ? AND accepted_at IS NULL AND revoked_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(&email)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let token = generate_token();
+    let expires_at = Utc::now() + chrono::Duration::hours(data.env.invitation_ttl);
+    let result = sqlx::query(
+        r#"INSERT INTO invitations (email, role, token_hash, invited_by, expires_at) VALUES (?, ?, ?, ?, ?)"#,
+    )
+    .bind(&email)
+    .bind(body.role.as_str())
+    .bind(hash_token(&token))
+    .bind(admin.id)
+    .bind(expires_at)
+    .execute(&data.db)
+    .await
+    .map_err(db_error)?;
+
+    let invitation_email = Email {
+        to: email.clone(),
+        subject: "You're invited to the helpdesk".to_string(),
+        body: format!(
+            "{} invited you to create an account. The link below expires in {} hours.\n\n{}/register?invitation={}",
+            admin.name.clone().unwrap_or_default(),
+            data.env.invitation_ttl,
+            data.env.app_url,
+            token
+        ),
+    };
+    if let Err(e) = send_email(data.mailer.clone(), invitation_email).await {
+        println!("🔥 {}", e);
+    }
+
+    Ok((StatusCode::CREATED, Json(json!({
+        "status": "success",
+        "id": result.last_insert_id(),
+        "email": email,
+        "role": body.role,
+        "expires_at": expires_at,
+    }))))
+}
+
+pub async fn revoke_invitation_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = sqlx::query(r#"UPDATE invitations SET revoked_at = ? WHERE id = ? AND accepted_at IS NULL AND revoked_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    if result.rows_affected() == 0 {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": format!("No pending invitation with ID: {}", id),
+        });
+        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,3 +1,12 @@
 pub mod auth_handlers;
 pub mod ticket_handlers;
-pub mod comment_handlers;
\ No newline at end of file
+pub mod comment_handlers;
+pub mod totp_handlers;
+pub mod token_handlers;
+pub mod user_handlers;
+pub mod audit_handlers;
+pub mod invitation_handlers;
+pub mod oidc_handlers;
+pub mod session_handlers;
+pub mod tag_handlers;
+pub mod view_handlers;
\ No newline at end of file
@@ -0,0 +1,347 @@
+use std::sync::Arc;
+use chrono::prelude::*;
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Json
+};
+use axum_extra::extract::cookie::CookieJar;
+
+use serde_json::json;
+use crate::{
+    handlers::auth_handlers::{db_error, login_blocked, start_session},
+    model::{LoginModel, Role},
+    schema::OidcCallbackQuery,
+    utils::{
+        audit::{AuditEvent, AuditRecord, ClientInfo},
+        cookies::{oidc_flow_cookie, oidc_flow_removal_cookie, OIDC_FLOW_COOKIE},
+        jwt::{oidc_flow_decode, oidc_flow_encode},
+        oidc::{generate_pkce, OidcClient, OidcError, OidcIdentity},
+        token::generate_token,
+    },
+    AppState
+};
+
+// OIDC Handlers --------------------------------------------
+
+// Starts a single sign-on login: remembers state, nonce and PKCE verifier in a
+// short-lived cookie and sends the browser to the provider
+pub async fn oidc_login_handler(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let (authorization_url, cookie) = start_flow(&data, None).await?;
+
+    Ok((
+        [(header::SET_COOKIE, cookie)],
+        Redirect::to(&authorization_url),
+    ))
+}
+
+// Links single sign-on to the logged in account, whatever email the provider
+// has. Answers the provider URL for the app to send the browser to; the
+// callback then links instead of logging in.
+pub async fn oidc_link_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let (authorization_url, cookie) = start_flow(&data, Some(user.id)).await?;
+
+    Ok((
+        [(header::SET_COOKIE, cookie)],
+        Json(json!({"authorization_url": authorization_url})),
+    ))
+}
+
+// Provider URL and the flow cookie remembering state, nonce and PKCE verifier
+async fn start_flow(
+    data: &AppState,
+    link_user_id: Option<i64>,
+) -> Result<(String, String), (StatusCode, Json<serde_json::Value>)> {
+    let oidc = oidc_client(data)?;
+    let state = generate_token();
+    let nonce = generate_token();
+    let (code_verifier, code_challenge) = generate_pkce();
+
+    let authorization_url = oidc
+        .authorization_url(&state, &nonce, &code_challenge)
+        .await
+        .map_err(provider_error)?;
+    let flow = oidc_flow_encode(state, nonce, code_verifier, link_user_id, &data.keys);
+    let cookie = oidc_flow_cookie(&data.env, flow, time::Duration::minutes(10));
+    Ok((authorization_url, cookie.to_string()))
+}
+
+// The provider redirects back here. On success the session cookies are set
+// exactly like after a password login and the browser goes on to the app.
+pub async fn oidc_callback_handler(
+    State(data): State<Arc<AppState>>,
+    cookie_jar: CookieJar,
+    client: ClientInfo,
+    Query(params): Query<OidcCallbackQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let oidc = oidc_client(&data)?;
+    let flow = cookie_jar
+        .get(OIDC_FLOW_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .unwrap_or_default();
+    let flow = oidc_flow_decode(&flow, &data.keys)
+        .map_err(|(status, Json(e))| {
+            (status, Json(json!({"status": "fail", "message": e.message})))
+        })?;
+
+    if let Some(error) = &params.error {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": format!(
+                "The provider refused the login: {}",
+                params.error_description.as_deref().unwrap_or(error)
+            ),
+        });
+        return Err((StatusCode::UNAUTHORIZED, Json(error_response)));
+    }
+    let code = match (&params.code, &params.state) {
+        (Some(code), Some(state)) if *state == flow.state => code,
+        _ => {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "Single sign-on attempt expired or invalid, please try again",
+            });
+            return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+        }
+    };
+
+    let identity = oidc
+        .exchange_code(code, &flow.code_verifier, &flow.nonce)
+        .await
+        .map_err(provider_error)?;
+
+    if let Some(user_id) = flow.link_user_id {
+        link_user(&data, user_id, &identity, &client).await?;
+        return Ok(redirect_after_flow(&data, StatusCode::SEE_OTHER.into_response()));
+    }
+    let user = link_or_create_user(&data, oidc, &identity, &client).await?;
+
+    if let Some(error_response) = login_blocked(&user) {
+        AuditRecord::new(AuditEvent::LoginFailure, &client)
+            .user(user.id)
+            .email(&identity.email)
+            .detail(format!("single sign-on: {}", error_response["message"].as_str().unwrap_or_default()))
+            .record(&data.db)
+            .await;
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+
+    // the provider takes care of the second factor. Accounts with a password or
+    // their own second factor are only ever linked by their owner.
+    let response = start_session(&data, &user, &client).await?;
+    AuditRecord::new(AuditEvent::LoginSuccess, &client)
+        .user(user.id)
+        .email(&identity.email)
+        .detail("single sign-on")
+        .record(&data.db)
+        .await;
+
+    Ok(redirect_after_flow(&data, response.into_response()))
+}
+
+fn redirect_after_flow(data: &AppState, mut response: Response) -> Response {
+    *response.status_mut() = StatusCode::SEE_OTHER;
+    let headers = response.headers_mut();
+    headers.insert(header::LOCATION, data.env.oidc_post_login_redirect.parse().unwrap());
+    headers.append(
+        header::SET_COOKIE,
+        oidc_flow_removal_cookie(&data.env).to_string().parse().unwrap(),
+    );
+    response
+}
+
+// Explicit link from oidc_link_handler. The subject can't already belong to
+// someone else.
+async fn link_user(
+    data: &AppState,
+    user_id: i64,
+    identity: &OidcIdentity,
+    client: &ClientInfo,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let user = find_user(data, user_id).await?;
+    if let Some(error_response) = login_blocked(&user) {
+        AuditRecord::new(AuditEvent::LoginFailure, client)
+            .user(user.id)
+            .email(&identity.email)
+            .detail(format!("single sign-on link: {}", error_response["message"].as_str().unwrap_or_default()))
+            .record(&data.db)
+            .await;
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+    let linked = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE oidc_subject = ?"#)
+        .bind(&identity.subject)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?;
+    if linked.is_some_and(|linked| linked.id != user.id) {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "This single sign-on account is already linked to another account",
+        });
+        return Err((StatusCode::CONFLICT, Json(error_response)));
+    }
+
+    sqlx::query(r#"UPDATE login SET oidc_subject = ? WHERE id = ?"#)
+        .bind(&identity.subject)
+        .bind(user.id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+    AuditRecord::new(AuditEvent::OidcLinked, client)
+        .user(user.id)
+        .email(&identity.email)
+        .record(&data.db)
+        .await;
+    Ok(())
+}
+
+// Finds the account for the identity: by the linked subject first, then by
+// email, otherwise a new account without a password. Going by email needs an
+// address the provider has verified, and only links accounts that have no
+// password and no second factor of their own. The role follows the
+// provider's groups whenever the ID token carries them.
+async fn link_or_create_user(
+    data: &AppState,
+    oidc: &OidcClient,
+    identity: &OidcIdentity,
+    client: &ClientInfo,
+) -> Result<LoginModel, (StatusCode, Json<serde_json::Value>)> {
+    let mapped_role = oidc.role_for(identity.groups.as_deref());
+
+    let by_subject = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE oidc_subject = ?"#)
+        .bind(&identity.subject)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?;
+    let user = match by_subject {
+        Some(user) => user,
+        None => {
+            if !identity.email_verified {
+                let error_response = serde_json::json!({
+                    "status": "fail",
+                    "message": "The provider hasn't verified this email address",
+                });
+                return Err((StatusCode::FORBIDDEN, Json(error_response)));
+            }
+            let by_email = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE email = ?"#)
+                .bind(&identity.email)
+                .fetch_optional(&data.db)
+                .await
+                .map_err(db_error)?;
+            match by_email {
+                Some(user) if user.oidc_subject.is_some() => {
+                    let error_response = serde_json::json!({
+                        "status": "fail",
+                        "message": "This email is already linked to another single sign-on account",
+                    });
+                    return Err((StatusCode::CONFLICT, Json(error_response)));
+                }
+                Some(user) if user.password.is_some() || user.totp_enabled_at.is_some() => {
+                    let error_response = serde_json::json!({
+                        "status": "fail",
+                        "message": "An account with this email already exists, log in with it and link single sign-on from there",
+                    });
+                    return Err((StatusCode::CONFLICT, Json(error_response)));
+                }
+                // the provider vouches for the address, so it counts as verified
+                Some(user) => {
+                    sqlx::query(
+                        r#"UPDATE login SET oidc_subject = ?, email_verified_at = COALESCE(email_verified_at, ?) WHERE id = ?"#,
+                    )
+                    .bind(&identity.subject)
+                    .bind(Utc::now())
+                    .bind(user.id)
+                    .execute(&data.db)
+                    .await
+                    .map_err(db_error)?;
+                    find_user(data, user.id).await?
+                }
+                None => return create_user(data, identity, mapped_role.unwrap_or(oidc.default_role())).await,
+            }
+        }
+    };
+
+    match mapped_role {
+        Some(role) if user.role.as_deref() != Some(role.as_str()) => {
+            sqlx::query(r#"UPDATE login SET role = ? WHERE id = ?"#)
+                .bind(role.as_str())
+                .bind(user.id)
+                .execute(&data.db)
+                .await
+                .map_err(db_error)?;
+            AuditRecord::new(AuditEvent::RoleChange, client)
+                .user(user.id)
+                .email(&identity.email)
+                .detail(format!("{} -> {} (single sign-on groups)", user.role.as_deref().unwrap_or("none"), role))
+                .record(&data.db)
+                .await;
+            find_user(data, user.id).await
+        }
+        _ => Ok(user),
+    }
+}
+
+async fn create_user(
+    data: &AppState,
+    identity: &OidcIdentity,
+    role: Role,
+) -> Result<LoginModel, (StatusCode, Json<serde_json::Value>)> {
+    if data.env.registration == "invite" {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Registration is by invitation only",
+        });
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+    // falls back to the part before the @ when the provider doesn't share a name
+    let name = identity
+        .name
+        .clone()
+        .unwrap_or_else(|| identity.email.split('@').next().unwrap_or_default().to_string());
+    let result = sqlx::query(
+        r#"INSERT INTO login (name, email, password, role, email_verified_at, oidc_subject) VALUES (?, ?, NULL, ?, ?, ?)"#,
+    )
+    .bind(name)
+    .bind(&identity.email)
+    .bind(role.as_str())
+    .bind(Utc::now())
+    .bind(&identity.subject)
+    .execute(&data.db)
+    .await
+    .map_err(db_error)?;
+    find_user(data, result.last_insert_id() as i64).await
+}
+
+async fn find_user(data: &AppState, id: i64) -> Result<LoginModel, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE id = ?"#)
+        .bind(id)
+        .fetch_one(&data.db)
+        .await
+        .map_err(db_error)
+}
+
+fn oidc_client(data: &AppState) -> Result<&OidcClient, (StatusCode, Json<serde_json::Value>)> {
+    data.oidc.as_ref().ok_or_else(|| {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Single sign-on is not configured",
+        });
+        (StatusCode::NOT_FOUND, Json(error_response))
+    })
+}
+
+// Details go to the log, the browser only learns that it failed
+fn provider_error(e: OidcError) -> (StatusCode, Json<serde_json::Value>) {
+    println!("🔥 {}", e);
+    let error_response = serde_json::json!({
+        "status": "fail",
+        "message": "Single sign-on failed, please try again",
+    });
+    (StatusCode::BAD_GATEWAY, Json(error_response))
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json
+};
+
+use serde_json::json;
+use crate::{
+    handlers::auth_handlers::db_error,
+    model::{LoginModel, SessionModel, TokenClaims},
+    utils::{
+        audit::{AuditEvent, AuditRecord, ClientInfo},
+        session::{active_sessions, find_session, revoke_session},
+    },
+    AppState
+};
+
+// Session Handlers -----------------------------------------
+
+// The caller's active sessions, the one making the request marked `current`
+pub async fn session_list_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+    Extension(claims): Extension<TokenClaims>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut sessions = active_sessions(&data.db, user.id).await.map_err(db_error)?;
+    for session in sessions.iter_mut() {
+        session.current = claims.sid.as_deref() == Some(session.id.as_str());
+    }
+
+    Ok(Json(json!(sessions)))
+}
+
+// Logs one of the caller's devices out. Revoking the current session works too.
+pub async fn revoke_session_handler(
+    Path(id): Path<String>,
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    revoke_user_session(&data, &client, user.id, &id, None).await
+}
+
+pub async fn user_session_list_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let sessions = active_sessions(&data.db, id).await.map_err(db_error)?;
+
+    Ok(Json(json!(sessions)))
+}
+
+pub async fn revoke_user_session_handler(
+    Path((id, session_id)): Path<(i64, String)>,
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(admin): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    revoke_user_session(&data, &client, id, &session_id, Some(admin.id)).await
+}
+
+// 404 unless the session belongs to `user_id` and is still active
+async fn revoke_user_session(
+    data: &AppState,
+    client: &ClientInfo,
+    user_id: i64,
+    session_id: &str,
+    actor_id: Option<i64>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let session = find_session(&data.db, session_id)
+        .await
+        .map_err(db_error)?
+        .filter(|session: &SessionModel| session.user_id == user_id);
+    let revoked = match session {
+        Some(session) => revoke_session(&data.db, &session.id).await.map_err(db_error)?,
+        None => false,
+    };
+    if !revoked {
+        let error_response = serde_json::json!({
+            "status": "error",
+            "message": format!("Session with ID: {} not found", session_id)
+        });
+        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    }
+
+    let mut audit = AuditRecord::new(AuditEvent::SessionRevoked, client)
+        .user(user_id)
+        .detail(session_id);
+    if let Some(actor_id) = actor_id {
+        audit = audit.actor(actor_id);
+    }
+    audit.record(&data.db).await;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json
+};
+
+use serde_json::json;
+use sqlx::{MySql, QueryBuilder};
+use crate::{
+    handlers::{
+        auth_handlers::db_error,
+        ticket_handlers::{fetch_ticket, filter_db_record},
+    },
+    model::TagModel,
+    schema::{TagSchema, TicketTagsSchema},
+    AppState
+};
+
+// Tag Handlers ---------------------------------------------
+
+pub async fn tag_list_handler(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let tags = sqlx::query_as::<_, TagModel>(r#"SELECT * FROM tags ORDER BY name"#)
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(json!(tags)))
+}
+
+pub async fn create_tag_handler(
+    State(data): State<Arc<AppState>>,
+    Json(body): Json<TagSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let name = tag_name(&body.name)?;
+    let result = sqlx::query(r#"INSERT INTO tags (name) VALUES (?)"#)
+        .bind(&name)
+        .execute(&data.db)
+        .await
+        .map_err(|e| tag_write_error(e, &name))?;
+
+    let tag = find_tag(&data, result.last_insert_id() as i64).await?;
+    Ok((StatusCode::CREATED, Json(json!(tag))))
+}
+
+// Renaming keeps the tag on every ticket it is on
+pub async fn rename_tag_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    Json(body): Json<TagSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let name = tag_name(&body.name)?;
+    find_tag(&data, id).await?;
+    sqlx::query(r#"UPDATE tags SET name = ? WHERE id = ?"#)
+        .bind(&name)
+        .bind(id)
+        .execute(&data.db)
+        .await
+        .map_err(|e| tag_write_error(e, &name))?;
+
+    let tag = find_tag(&data, id).await?;
+    Ok(Json(json!(tag)))
+}
+
+// Also takes the tag off every ticket
+pub async fn delete_tag_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = sqlx::query(r#"DELETE FROM tags WHERE id = ?"#)
+        .bind(id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+    if result.rows_affected() == 0 {
+        return Err(tag_not_found(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Adds tags to a ticket. Tags it already has are left alone.
+pub async fn add_ticket_tags_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    Json(body): Json<TicketTagsSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ticket_exists(&data, id).await?;
+    if body.tag_ids.is_empty() {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "tag_ids can't be empty",
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    for tag_id in &body.tag_ids {
+        find_tag(&data, *tag_id).await?;
+    }
+
+    let mut query = QueryBuilder::<MySql>::new("INSERT IGNORE INTO ticket_tags (ticket_id, tag_id) ");
+    query.push_values(&body.tag_ids, |mut row, tag_id| {
+        row.push_bind(id).push_bind(*tag_id);
+    });
+    query.build().execute(&data.db).await.map_err(db_error)?;
+
+    ticket_response(&data, id).await
+}
+
+pub async fn remove_ticket_tag_handler(
+    Path((id, tag_id)): Path<(i64, i64)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ticket_exists(&data, id).await?;
+    sqlx::query(r#"DELETE FROM ticket_tags WHERE ticket_id = ? AND tag_id = ?"#)
+        .bind(id)
+        .bind(tag_id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    ticket_response(&data, id).await
+}
+
+// Trimmed and lowercased, so "Billing" and "billing " are the same tag. No
+// commas, the `tag` filter of the ticket list is comma separated.
+fn tag_name(name: &str) -> Result<String, (StatusCode, Json<serde_json::Value>)> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty() || name.chars().count() > 64 || name.contains(',') {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "A tag name needs 1 to 64 characters and can't contain commas",
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    Ok(name)
+}
+
+async fn find_tag(data: &AppState, id: i64) -> Result<TagModel, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as::<_, TagModel>(r#"SELECT * FROM tags WHERE id = ?"#)
+        .bind(id)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| tag_not_found(id))
+}
+
+fn tag_write_error(e: sqlx::Error, name: &str) -> (StatusCode, Json<serde_json::Value>) {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": format!("Tag `{}` already exists", name),
+            });
+            (StatusCode::CONFLICT, Json(error_response))
+        }
+        e => db_error(e),
+    }
+}
+
+fn tag_not_found(id: i64) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = serde_json::json!({
+        "status": "fail",
+        "message": format!("Tag with ID: {} not found", id)
+    });
+    (StatusCode::NOT_FOUND, Json(error_response))
+}
+
+async fn ticket_exists(data: &AppState, id: i64) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match fetch_ticket(&data.db, id).await {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::RowNotFound) => {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": format!("Ticket with ID: {} not found", id)
+            });
+            Err((StatusCode::NOT_FOUND, Json(error_response)))
+        }
+        Err(e) => Err(db_error(e)),
+    }
+}
+
+async fn ticket_response(data: &AppState, id: i64) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let ticket = fetch_ticket(&data.db, id).await.map_err(db_error)?;
+    Ok(Json(json!({
+        "ticket": filter_db_record(&ticket),
+        "status": "success",
+    })))
+}
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 use chrono::prelude::*;
 use axum::{
-    extract::{Path, Query, State},
+    extract::{rejection::QueryRejection, Path, Query, State},
     http::StatusCode,
     body::Body,
     response::IntoResponse, 
@@ -9,32 +9,69 @@ use axum::{
 };
 
 use serde_json::{json, Value};
-use sqlx::MySqlPool;
+use sqlx::{MySql, MySqlPool, QueryBuilder};
 use crate::{
     error::AppError, 
-    model::{TicketModel, TicketModelResponse}, 
-    schema::{CreateTicketSchema, FilterOptions, UpdateTicketSchema}, 
+    handlers::auth_handlers::db_error,
+    model::{LoginModel, Role, TicketModel, TicketModelResponse, TicketPriority, TicketStatus}, 
+    schema::{AssignTicketSchema, CreateTicketSchema, ReassignTicketSchema, TicketFilterOptions, UpdateTicketSchema}, 
     AppState
 };
 
+// Tickets with the name of their assignee. Tags come from load_tags.
+pub(crate) const TICKET_SELECT: &str = "SELECT tickets.*, login.name AS assignee_name \
+    FROM tickets LEFT JOIN login ON login.id = tickets.assignee_id";
+
+// `sort` field -> column. Nothing outside this list ends up in ORDER BY.
+// Status sorts in workflow order and priority from low to urgent.
+const TICKET_SORT_COLUMNS: &[(&str, &str)] = &[
+    ("id", "tickets.id"),
+    ("summary", "tickets.summary"),
+    ("status", "FIELD(tickets.status, 'created', 'in_progress', 'resolved', 'closed')"),
+    ("priority", "FIELD(tickets.priority, 'low', 'medium', 'high', 'urgent')"),
+    ("created_at", "tickets.create_date"),
+    ("updated_at", "tickets.update_date"),
+];
 
 // Ticket Handlers ------------------------------------------
 pub async fn ticket_list_handler(
-    opts: Option<Query<FilterOptions>>,
+    opts: Result<Query<TicketFilterOptions>, QueryRejection>,
     State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let Query(opts) = opts.unwrap_or_default();
-    
-    let limit = opts.limit.unwrap_or(20);
-    let offset = (opts.page.unwrap_or(1) - 1) * limit;
-    
-    let tickets = sqlx::query_as!(
-        TicketModel,
-        r#"SELECT * FROM tickets ORDER by create_date DESC LIMIT ? OFFSET ?"#,
-        limit as i64,
-        offset as i64
-    )
-    .fetch_all(&data.db)
+    // a malformed parameter must not quietly drop every filter
+    let Query(opts) = opts.map_err(|e| invalid_filter(&e.body_text()))?;
+    let ticket_responses = list_tickets(&data.db, &opts, &user).await?;
+
+    let json_response = serde_json::json!(ticket_responses);
+    //     "status": "success",
+    //     "results": ticket_responses.len(),
+    //     "tickets": ticket_responses
+    // });
+
+Ok(Json(json_response))
+}
+
+// One page of the ticket list for `user`. Saved views run through here as well,
+// so both always filter and sort the same way.
+pub(crate) async fn list_tickets(
+    db: &MySqlPool,
+    opts: &TicketFilterOptions,
+    user: &LoginModel,
+) -> Result<Vec<TicketModelResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let (limit, offset) = ticket_page(opts);
+
+    let mut query = QueryBuilder::<MySql>::new(TICKET_SELECT);
+    push_ticket_filters(&mut query, opts, user)?;
+    push_ticket_sort(&mut query, opts.sort.as_deref())?;
+    query.push(" LIMIT ");
+    query.push_bind(limit as i64);
+    query.push(" OFFSET ");
+    query.push_bind(offset as i64);
+
+    let mut tickets = query
+    .build_query_as::<TicketModel>()
+    .fetch_all(db)
         .await
         .map_err(|e| {
             let error_response = serde_json::json!({
@@ -43,19 +80,31 @@ pub async fn ticket_list_handler(
             });
             (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
     })?;
-    
-    let ticket_responses = tickets
+    load_tags(db, &mut tickets).await.map_err(db_error)?;
+
+    Ok(tickets
     .iter()
     .map(|ticket| filter_db_record(&ticket))
-    .collect::<Vec<TicketModelResponse>>();
+    .collect::<Vec<TicketModelResponse>>())
+}
 
-    let json_response = serde_json::json!(ticket_responses);
-    //     "status": "success",
-    //     "results": ticket_responses.len(),
-    //     "tickets": ticket_responses
-    // });
+// LIMIT and OFFSET: 20 per page by default, at most 100. Pages below 1 are
+// the first page.
+pub(crate) fn ticket_page(opts: &TicketFilterOptions) -> (usize, usize) {
+    let limit = opts.limit.unwrap_or(20).clamp(1, 100);
+    let page = opts.page.unwrap_or(1).max(1);
+    // capped so it still fits the i64 it is bound as
+    (limit, (page - 1).saturating_mul(limit).min(i64::MAX as usize))
+}
 
-Ok(Json(json_response))
+// The same checks list_tickets makes, without running the query
+pub(crate) fn validate_ticket_filters(
+    opts: &TicketFilterOptions,
+    user: &LoginModel,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let mut query = QueryBuilder::<MySql>::new(TICKET_SELECT);
+    push_ticket_filters(&mut query, opts, user)?;
+    push_ticket_sort(&mut query, opts.sort.as_deref())
 }
 
 pub async fn create_ticket_handler(
@@ -65,8 +114,8 @@ pub async fn create_ticket_handler(
     let query_result =
         sqlx::query(r#"INSERT INTO tickets (summary, priority, status) VALUES (?, ?, ?)"#)
             .bind(body.summary.to_string())
-            .bind(body.priority.to_string())
-            .bind(body.status.to_string())
+            .bind(body.priority)
+            .bind(TicketStatus::Created)
             .execute(&data.db)
             .await
             .map_err(|err: sqlx::Error| err.to_string());
@@ -95,13 +144,7 @@ pub async fn get_ticket_handler(
     Path(id): Path<i64>,
     State(data): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query_result = sqlx::query_as!(
-        TicketModel,
-        r#"SELECT * FROM tickets WHERE id = ?"#,
-        id.to_string()
-    )
-    .fetch_one(&data.db)
-    .await;
+    let query_result = fetch_ticket(&data.db, id).await;
 
 
     match query_result {
@@ -131,13 +174,7 @@ pub async fn edit_ticket_handler(
     State(data): State<Arc<AppState>>,
     Json(body): Json<UpdateTicketSchema>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query_result = sqlx::query_as!(
-        TicketModel,
-        r#"SELECT * FROM tickets WHERE id = ?"#,
-        id.to_string()
-    )
-    .fetch_one(&data.db)
-    .await;
+    let query_result = fetch_ticket(&data.db, id).await;
 
     let ticket = match query_result {
         Ok(ticket) => ticket,
@@ -156,19 +193,33 @@ pub async fn edit_ticket_handler(
         }
     };
 
+    let status = body.status.unwrap_or(ticket.status);
+    if !ticket.status.can_move_to(status) {
+        let allowed: Vec<&str> = ticket.status.next_states().iter().map(TicketStatus::as_str).collect();
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": format!(
+                "Can't move a ticket from `{}` to `{}`, allowed next states: {}",
+                ticket.status,
+                status,
+                allowed.join(", ")
+            ),
+            "allowed": allowed,
+        });
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(error_response)));
+    }
+
+    // only if the status is still the one the transition was checked against,
+    // so two concurrent edits can't chain into an illegal jump
     let update_result = sqlx::query(
-        r#"UPDATE tickets SET summary = ?, status = ?, priority = ?, update_date = ? WHERE id = ?"#,
+        r#"UPDATE tickets SET summary = ?, status = ?, priority = ?, update_date = ? WHERE id = ? AND status = ?"#,
     )
     .bind(body.summary.to_owned().unwrap_or_else(|| ticket.summary.clone()))
-    .bind(
-        body.status
-            .to_owned()
-            .unwrap_or_else(|| ticket.status.clone()))
-    .bind(body.priority
-        .to_owned()
-        .unwrap_or_else(|| ticket.priority.clone()))
+    .bind(status)
+    .bind(body.priority.unwrap_or(ticket.priority))
     .bind(chrono::offset::Utc::now())
     .bind(id.to_string())
+    .bind(ticket.status)
     .execute(&data.db)
     .await
     .map_err(|e| {
@@ -179,19 +230,29 @@ pub async fn edit_ticket_handler(
     })?;
 
     if update_result.rows_affected() == 0 {
-        let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("Ticket with ID: {} not found", id)
+        return Err(match fetch_ticket(&data.db, id).await {
+            Ok(current) => {
+                let error_response = serde_json::json!({
+                    "status": "fail",
+                    "message": format!(
+                        "Ticket status changed to `{}` in the meantime, reload it and try again",
+                        current.status
+                    ),
+                });
+                (StatusCode::CONFLICT, Json(error_response))
+            }
+            Err(sqlx::Error::RowNotFound) => {
+                let error_response = serde_json::json!({
+                    "status": "error",
+                    "message": format!("Ticket with ID: {} not found", id)
+                });
+                (StatusCode::NOT_FOUND, Json(error_response))
+            }
+            Err(e) => db_error(e),
         });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
     }
 
-    let updated_ticket = sqlx::query_as!(
-        TicketModel,
-        r#"SELECT * FROM tickets WHERE id = ?"#,
-        id.to_string()
-    )
-    .fetch_one(&data.db)
+    let updated_ticket = fetch_ticket(&data.db, id)
     .await
     .map_err(|e| {
         (
@@ -234,18 +295,295 @@ pub async fn delete_ticket_handler(
 }
 
 
-fn filter_db_record(ticket: &TicketModel) -> TicketModelResponse {
+fn push_ticket_filters(
+    query: &mut QueryBuilder<'_, MySql>,
+    opts: &TicketFilterOptions,
+    user: &LoginModel,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    query.push(" WHERE 1 = 1");
+    match opts.assignee.as_deref() {
+        None => {}
+        Some("none") => {
+            query.push(" AND tickets.assignee_id IS NULL");
+        }
+        Some("me") => {
+            query.push(" AND tickets.assignee_id = ");
+            query.push_bind(user.id);
+        }
+        Some(assignee) => {
+            let assignee_id = assignee.parse::<i64>().map_err(|_| {
+                invalid_filter("assignee must be a user id, `me` or `none`")
+            })?;
+            query.push(" AND tickets.assignee_id = ");
+            query.push_bind(assignee_id);
+        }
+    }
+
+    let mut tags = comma_separated(opts.tag.as_deref());
+    tags.sort();
+    tags.dedup();
+    if !tags.is_empty() {
+        let match_all = match opts.tag_match.as_deref() {
+            None | Some("any") => false,
+            Some("all") => true,
+            Some(_) => return Err(invalid_filter("tag_match must be `any` or `all`")),
+        };
+        // with `all` every named tag has to be on the ticket, with `any` one is enough
+        query.push(if match_all { " AND (SELECT COUNT(DISTINCT tags.id)" } else { " AND EXISTS (SELECT 1" });
+        query.push(
+            " FROM ticket_tags JOIN tags ON tags.id = ticket_tags.tag_id \
+            WHERE ticket_tags.ticket_id = tickets.id AND tags.name IN (",
+        );
+        let mut names = query.separated(", ");
+        for tag in &tags {
+            names.push_bind(tag.clone());
+        }
+        query.push(")");
+        if match_all {
+            query.push(") = ");
+            query.push_bind(tags.len() as i64);
+        } else {
+            query.push(")");
+        }
+    }
+
+    let statuses = parse_all::<TicketStatus>(opts.status.as_deref())?;
+    let priorities = parse_all::<TicketPriority>(opts.priority.as_deref())?;
+    let statuses: Vec<&str> = statuses.iter().map(TicketStatus::as_str).collect();
+    let priorities: Vec<&str> = priorities.iter().map(TicketPriority::as_str).collect();
+    for (column, values) in [("tickets.status", statuses), ("tickets.priority", priorities)] {
+        if values.is_empty() {
+            continue;
+        }
+        query.push(format!(" AND {} IN (", column));
+        let mut separated = query.separated(", ");
+        for value in values {
+            separated.push_bind(value);
+        }
+        query.push(")");
+    }
+
+    if let Some(q) = opts.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        query.push(" AND tickets.summary LIKE ");
+        query.push_bind(pattern);
+    }
+
+    let date_ranges = [
+        ("tickets.create_date >= ", opts.created_from),
+        ("tickets.create_date <= ", opts.created_to),
+        ("tickets.update_date >= ", opts.updated_from),
+        ("tickets.update_date <= ", opts.updated_to),
+    ];
+    for (condition, date) in date_ranges {
+        if let Some(date) = date {
+            query.push(" AND ");
+            query.push(condition);
+            query.push_bind(date);
+        }
+    }
+    Ok(())
+}
+
+// ORDER BY from `sort`, newest first by default. Ties are broken by id so
+// pages don't overlap.
+fn push_ticket_sort(
+    query: &mut QueryBuilder<'_, MySql>,
+    sort: Option<&str>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let sort = sort.map(str::trim).filter(|sort| !sort.is_empty()).unwrap_or("-created_at");
+    query.push(" ORDER BY ");
+    for field in sort.split(',').map(str::trim) {
+        let (name, direction) = match field.strip_prefix('-') {
+            Some(name) => (name, " DESC"),
+            None => (field.strip_prefix('+').unwrap_or(field), " ASC"),
+        };
+        let column = TICKET_SORT_COLUMNS
+            .iter()
+            .find(|(allowed, _)| *allowed == name)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| {
+                let allowed: Vec<&str> = TICKET_SORT_COLUMNS.iter().map(|(allowed, _)| *allowed).collect();
+                invalid_filter(&format!("Can't sort by `{}`, use one of: {}", name, allowed.join(", ")))
+            })?;
+        query.push(column);
+        query.push(direction);
+        query.push(", ");
+    }
+    query.push("tickets.id DESC");
+    Ok(())
+}
+
+fn comma_separated(values: Option<&str>) -> Vec<String> {
+    values
+        .unwrap_or_default()
+        .split(',')
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+// Every comma separated value parsed, 400 on the first one that isn't valid
+fn parse_all<T: std::str::FromStr<Err = String>>(
+    values: Option<&str>,
+) -> Result<Vec<T>, (StatusCode, Json<serde_json::Value>)> {
+    comma_separated(values)
+        .iter()
+        .map(|value| value.parse::<T>().map_err(|e| invalid_filter(&e)))
+        .collect()
+}
+
+pub(crate) fn invalid_filter(message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = serde_json::json!({
+        "status": "fail",
+        "message": message,
+    });
+    (StatusCode::BAD_REQUEST, Json(error_response))
+}
+
+// Takes an unassigned ticket, for the caller unless `assignee_id` says otherwise.
+// Already assigned tickets answer 409, use reassign to take them over.
+pub async fn assign_ticket_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+    Json(body): Json<AssignTicketSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let assignee_id = body.assignee_id.unwrap_or(user.id);
+    check_assignee(&data.db, assignee_id).await?;
+    set_assignee(&data.db, id, Some(assignee_id), true).await
+}
+
+pub async fn reassign_ticket_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    Json(body): Json<ReassignTicketSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    check_assignee(&data.db, body.assignee_id).await?;
+    set_assignee(&data.db, id, Some(body.assignee_id), false).await
+}
+
+pub async fn unassign_ticket_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    set_assignee(&data.db, id, None, false).await
+}
+
+// Only active agents and admins work on tickets
+async fn check_assignee(db: &MySqlPool, assignee_id: i64) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let assignee = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE id = ?"#)
+        .bind(assignee_id)
+        .fetch_optional(db)
+        .await
+        .map_err(db_error)?;
+    match assignee {
+        Some(assignee)
+            if assignee.deactivated_at.is_none()
+                && matches!(assignee.role(), Some(Role::Admin | Role::Agent)) => Ok(()),
+        _ => {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "Tickets can only be assigned to active agents and admins",
+            });
+            Err((StatusCode::BAD_REQUEST, Json(error_response)))
+        }
+    }
+}
+
+// `only_if_unassigned` makes two agents taking the same ticket at once safe:
+// the second one gets a 409
+async fn set_assignee(
+    db: &MySqlPool,
+    id: i64,
+    assignee_id: Option<i64>,
+    only_if_unassigned: bool,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let ticket = match fetch_ticket(db, id).await {
+        Ok(ticket) => ticket,
+        Err(sqlx::Error::RowNotFound) => {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": format!("Ticket with ID: {} not found", id)
+            });
+            return Err((StatusCode::NOT_FOUND, Json(error_response)));
+        }
+        Err(e) => return Err(db_error(e)),
+    };
+
+    let mut query = QueryBuilder::<MySql>::new("UPDATE tickets SET assignee_id = ");
+    query.push_bind(assignee_id);
+    query.push(", update_date = ");
+    query.push_bind(Utc::now());
+    query.push(" WHERE id = ");
+    query.push_bind(ticket.id);
+    if only_if_unassigned {
+        query.push(" AND assignee_id IS NULL");
+    }
+    let update_result = query.build().execute(db).await.map_err(db_error)?;
+    if only_if_unassigned && update_result.rows_affected() == 0 {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Ticket is already assigned, reassign it instead",
+        });
+        return Err((StatusCode::CONFLICT, Json(error_response)));
+    }
+
+    let updated_ticket = fetch_ticket(db, id).await.map_err(db_error)?;
+    Ok(Json(json!({
+        "ticket": filter_db_record(&updated_ticket),
+        "status": "success",
+    })))
+}
+
+pub(crate) fn filter_db_record(ticket: &TicketModel) -> TicketModelResponse {
     TicketModelResponse {
         id: ticket.id.to_owned(),
         // title: ticket.title.to_owned(),
         summary: ticket.summary.to_owned(),
-        status: ticket.status.to_owned(),
-        priority: ticket.priority.to_owned(),
+        status: ticket.status,
+        priority: ticket.priority,
         createdAt: ticket.create_date.unwrap(),
         updatedAt: ticket.update_date.unwrap(),
+        assigneeId: ticket.assignee_id,
+        assigneeName: ticket.assignee_name.to_owned(),
+        tags: ticket.tags.clone(),
     }
 }
 
+pub(crate) async fn fetch_ticket(db: &MySqlPool, id: i64) -> Result<TicketModel, sqlx::Error> {
+    let mut ticket = sqlx::query_as::<_, TicketModel>(&format!("{} WHERE tickets.id = ?", TICKET_SELECT))
+        .bind(id)
+        .fetch_one(db)
+        .await?;
+    load_tags(db, std::slice::from_mut(&mut ticket)).await?;
+    Ok(ticket)
+}
+
+// Tag names of a whole page of tickets in one query
+async fn load_tags(db: &MySqlPool, tickets: &mut [TicketModel]) -> Result<(), sqlx::Error> {
+    if tickets.is_empty() {
+        return Ok(());
+    }
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT ticket_tags.ticket_id, tags.name FROM ticket_tags \
+        JOIN tags ON tags.id = ticket_tags.tag_id WHERE ticket_tags.ticket_id IN (",
+    );
+    let mut ids = query.separated(", ");
+    for ticket in tickets.iter() {
+        ids.push_bind(ticket.id);
+    }
+    query.push(") ORDER BY tags.name");
+
+    let rows = query.build_query_as::<(i64, String)>().fetch_all(db).await?;
+    for (ticket_id, name) in rows {
+        if let Some(ticket) = tickets.iter_mut().find(|ticket| ticket.id == ticket_id) {
+            ticket.tags.push(name);
+        }
+    }
+    Ok(())
+}
+
 pub async fn health_checker_handler() -> impl IntoResponse {
     const MESSAGE: &str = "Rust CRUD API Example with Axum Framework and MySQL";
 
@@ -256,3 +594,70 @@ pub async fn health_checker_handler() -> impl IntoResponse {
 
     Json(json_response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order_by(sort: Option<&str>) -> Result<String, StatusCode> {
+        let mut query = QueryBuilder::<MySql>::new("SELECT * FROM tickets");
+        push_ticket_sort(&mut query, sort).map_err(|(status, _)| status)?;
+        Ok(query.sql().trim_start_matches("SELECT * FROM tickets").to_string())
+    }
+
+    fn page(page: Option<usize>, limit: Option<usize>) -> (usize, usize) {
+        ticket_page(&TicketFilterOptions { page, limit, ..Default::default() })
+    }
+
+    #[test]
+    fn page_defaults_to_the_first_twenty() {
+        assert_eq!(page(None, None), (20, 0));
+        assert_eq!(page(Some(3), Some(10)), (10, 20));
+    }
+
+    #[test]
+    fn page_clamps_out_of_range_values() {
+        assert_eq!(page(Some(0), None), (20, 0));
+        assert_eq!(page(Some(1), Some(0)), (1, 0));
+        assert_eq!(page(Some(2), Some(100_000_000)), (100, 100));
+        assert_eq!(page(Some(usize::MAX), Some(100)), (100, i64::MAX as usize));
+    }
+
+    #[test]
+    fn sort_defaults_to_newest_first() {
+        assert_eq!(order_by(None).unwrap(), " ORDER BY tickets.create_date DESC, tickets.id DESC");
+        assert_eq!(order_by(Some("  ")).unwrap(), " ORDER BY tickets.create_date DESC, tickets.id DESC");
+    }
+
+    #[test]
+    fn sort_takes_several_fields_and_directions() {
+        assert_eq!(
+            order_by(Some("summary, -updated_at,+id")).unwrap(),
+            " ORDER BY tickets.summary ASC, tickets.update_date DESC, tickets.id ASC, tickets.id DESC"
+        );
+    }
+
+    #[test]
+    fn sort_rejects_fields_outside_the_allowlist() {
+        assert_eq!(order_by(Some("assignee_id")), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(order_by(Some("-created_at,summary; DROP TABLE tickets")), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(order_by(Some("id,")), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn sort_orders_status_and_priority_by_meaning() {
+        assert_eq!(
+            order_by(Some("-priority,status")).unwrap(),
+            " ORDER BY FIELD(tickets.priority, 'low', 'medium', 'high', 'urgent') DESC, \
+            FIELD(tickets.status, 'created', 'in_progress', 'resolved', 'closed') ASC, tickets.id DESC"
+        );
+    }
+
+    #[test]
+    fn status_and_priority_filters_only_take_known_values() {
+        let statuses = parse_all::<TicketStatus>(Some("Created, in_progress,,")).unwrap();
+        assert_eq!(statuses, vec![TicketStatus::Created, TicketStatus::InProgress]);
+        assert!(parse_all::<TicketPriority>(Some("high,urgnt")).is_err());
+        assert!(parse_all::<TicketStatus>(None).unwrap().is_empty());
+    }
+}
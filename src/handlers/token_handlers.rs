@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use chrono::prelude::*;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json
+};
+
+use serde_json::json;
+use crate::{
+    handlers::auth_handlers::db_error,
+    model::{ApiTokenModel, LoginModel},
+    schema::CreateApiTokenSchema,
+    utils::api_token::{create_api_token, SCOPES},
+    AppState
+};
+
+// API Token Handlers ---------------------------------------
+
+pub async fn api_token_list_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let tokens = sqlx::query_as::<_, ApiTokenModel>(
+        r#"SELECT * FROM api_tokens WHERE user_id = ? ORDER BY created_at DESC"#,
+    )
+    .bind(user.id)
+    .fetch_all(&data.db)
+    .await
+    .map_err(db_error)?;
+
+    Ok(Json(json!(tokens)))
+}
+
+pub async fn create_api_token_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+    Json(body): Json<CreateApiTokenSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if body.name.trim().is_empty() || body.scopes.is_empty() {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "A token needs a name and at least one scope",
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    if let Some(unknown) = body.scopes.iter().find(|scope| !SCOPES.contains(&scope.as_str())) {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": format!("Unknown scope `{}`, expected one of: {}", unknown, SCOPES.join(", ")),
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    if matches!(body.expires_in_days, Some(days) if days < 1) {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "expires_in_days must be at least 1",
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+
+    let (id, token) = create_api_token(
+        &data.db,
+        user.id,
+        body.name.trim(),
+        &body.scopes,
+        body.expires_in_days,
+    )
+    .await
+    .map_err(db_error)?;
+
+    // the only time the raw token is shown
+    Ok((StatusCode::CREATED, Json(json!({
+        "status": "success",
+        "id": id,
+        "token": token,
+    }))))
+}
+
+pub async fn revoke_api_token_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let query_result = sqlx::query(
+        r#"UPDATE api_tokens SET revoked_at = ? WHERE id = ? AND user_id = ? AND revoked_at IS NULL"#,
+    )
+    .bind(Utc::now())
+    .bind(id)
+    .bind(user.id)
+    .execute(&data.db)
+    .await
+    .map_err(db_error)?;
+
+    if query_result.rows_affected() == 0 {
+        let error_response = serde_json::json!({
+            "status": "error",
+            "message": format!("API token with ID: {} not found", id)
+        });
+        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use chrono::prelude::*;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json
+};
+
+use serde_json::json;
+use crate::{
+    handlers::auth_handlers::db_error,
+    model::LoginModel,
+    schema::TotpCodeSchema,
+    utils::totp::{consume_totp_code, generate_secret, provisioning_uri, replace_recovery_codes, verify_code},
+    AppState
+};
+
+// Two-factor Handlers --------------------------------------
+
+// Starts enrollment: stores a new secret that only takes effect once a code
+// generated from it is confirmed.
+pub async fn totp_enroll_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if user.totp_enabled_at.is_some() {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Two-factor authentication is already enabled",
+        });
+        return Err((StatusCode::CONFLICT, Json(error_response)));
+    }
+
+    let secret = generate_secret();
+    let account_name = user.email.clone().unwrap_or_else(|| user.id.to_string());
+    let otpauth_url = provisioning_uri(&secret, &data.env.totp_issuer, &account_name).ok_or_else(|| {
+        let error_response = serde_json::json!({
+            "status": "error",
+            "message": "Failed to create the provisioning URI",
+        });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+    })?;
+
+    sqlx::query(r#"UPDATE login SET totp_secret = ?, totp_last_step = 0 WHERE id = ?"#)
+        .bind(&secret)
+        .bind(user.id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "secret": secret,
+        "otpauth_url": otpauth_url,
+    })))
+}
+
+// Finishes enrollment with a first code and hands out the recovery codes. They
+// are only ever shown here.
+pub async fn totp_confirm_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+    Json(body): Json<TotpCodeSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let secret = match (&user.totp_secret, user.totp_enabled_at) {
+        (Some(secret), None) => secret,
+        _ => {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "No two-factor enrollment in progress",
+            });
+            return Err((StatusCode::CONFLICT, Json(error_response)));
+        }
+    };
+
+    let step = verify_code(secret, &body.code, user.totp_last_step, Utc::now().timestamp() as u64)
+        .ok_or_else(invalid_code)?;
+
+    sqlx::query(r#"UPDATE login SET totp_enabled_at = ?, totp_last_step = ? WHERE id = ?"#)
+        .bind(Utc::now())
+        .bind(step)
+        .bind(user.id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+    let recovery_codes = replace_recovery_codes(&data.db, user.id)
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "recovery_codes": recovery_codes,
+    })))
+}
+
+pub async fn totp_disable_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+    Json(body): Json<TotpCodeSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !consume_totp_code(&data.db, &user, &body.code).await.map_err(db_error)? {
+        return Err(invalid_code());
+    }
+
+    sqlx::query(r#"UPDATE login SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = 0 WHERE id = ?"#)
+        .bind(user.id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+    sqlx::query(r#"DELETE FROM totp_recovery_codes WHERE user_id = ?"#)
+        .bind(user.id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(json!({"status": "success"})))
+}
+
+fn invalid_code() -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = serde_json::json!({
+        "status": "fail",
+        "message": "Invalid two-factor code",
+    });
+    (StatusCode::BAD_REQUEST, Json(error_response))
+}
@@ -0,0 +1,392 @@
+use std::sync::Arc;
+use chrono::prelude::*;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json
+};
+
+use serde_json::json;
+use sqlx::{MySql, QueryBuilder};
+use crate::{
+    error::invalid_fields,
+    handlers::auth_handlers::{db_error, filter_user_record, send_password_reset, send_verification_email, start_session},
+    model::{FilteredUser, LoginModel},
+    schema::{ChangePasswordSchema, UpdateProfileSchema, UpdateUserRoleSchema, UserFilterOptions},
+    utils::{
+        api_token::revoke_all_api_tokens,
+        audit::{AuditEvent, AuditRecord, ClientInfo},
+        mailer::{send_email, Email},
+        password::{hash_password, verify_password},
+        revocation::revoke_all_sessions,
+    },
+    AppState
+};
+
+// Profile Handlers -----------------------------------------
+
+pub async fn update_me_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+    Json(body): Json<UpdateProfileSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    // every field is checked before anything is written
+    if let Some(name) = &body.name {
+        if name.trim().is_empty() {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "Name can't be empty",
+            });
+            return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+        }
+    }
+
+    let current_email = user.email.clone().unwrap_or_default();
+    let mut new_email = None;
+    if let Some(email) = body.email.as_deref().map(|email| email.trim().to_ascii_lowercase()) {
+        if !email.contains('@') {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": "Invalid email address",
+            });
+            return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+        }
+        if email != current_email.to_ascii_lowercase() {
+            let taken = sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE email = ? AND id <> ?"#)
+                .bind(&email)
+                .bind(user.id)
+                .fetch_optional(&data.db)
+                .await
+                .map_err(db_error)?;
+            if taken.is_some() {
+                let error_response = serde_json::json!({
+                    "status": "fail",
+                    "message": "An account with that email already exists",
+                });
+                return Err((StatusCode::CONFLICT, Json(error_response)));
+            }
+            new_email = Some(email);
+        }
+    }
+
+    let email_change_pending = new_email.is_some();
+    if let Some(email) = new_email {
+        send_verification_email(&data, user.id, &email, true)
+            .await
+            .map_err(|_| {
+                let error_response = serde_json::json!({
+                    "status": "error",
+                    "message": "Failed to send the verification email",
+                });
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+            })?;
+        // let the owner of the current address know, in case the session was hijacked
+        let notice = Email {
+            to: current_email.clone(),
+            subject: "Your email address is being changed".to_string(),
+            body: format!(
+                "A change of your account's email address to {} was requested. It takes effect once the new address is verified.",
+                email
+            ),
+        };
+        if let Err(e) = send_email(data.mailer.clone(), notice).await {
+            println!("🔥 {}", e);
+        }
+    }
+
+    if let Some(name) = &body.name {
+        sqlx::query(r#"UPDATE login SET name = ? WHERE id = ?"#)
+            .bind(name.trim())
+            .bind(user.id)
+            .execute(&data.db)
+            .await
+            .map_err(db_error)?;
+    }
+
+    let user = find_user(&data, user.id).await?;
+    Ok(Json(json!({
+        "status": "success",
+        "email_change_pending": email_change_pending,
+        "data": json!({
+            "user": filter_user_record(&user)
+        })
+    })))
+}
+
+// Logs out every other session: the current one gets fresh tokens in the response
+pub async fn change_password_handler(
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<LoginModel>,
+    Json(body): Json<ChangePasswordSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(fields) = data.password_policy.field_errors(
+        "new_password",
+        &body.new_password,
+        user.email.as_deref().unwrap_or_default(),
+        user.name.as_deref().unwrap_or_default(),
+    ) {
+        return Err(invalid_fields(fields));
+    }
+
+    // the login throttle also covers guessing the current password from a stolen session
+    let email = user.email.clone().unwrap_or_default();
+    let ip = client.ip.clone();
+    if let Err(wait) = data.login_throttle.check(&email, &ip) {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": format!("Too many failed attempts, try again in {} seconds", wait.as_secs().max(1)),
+            "retry_after": wait.as_secs().max(1),
+        });
+        return Err((StatusCode::TOO_MANY_REQUESTS, Json(error_response)));
+    }
+    let is_valid = verify_password(
+        data.password_hasher.clone(),
+        body.current_password.clone(),
+        user.password.clone().unwrap_or_default(),
+    )
+    .await;
+    if !is_valid {
+        data.login_throttle.record_failure(&email, &ip);
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "Current password is incorrect",
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    data.login_throttle.record_success(&email);
+
+    let hashed_password = hash_password(data.password_hasher.clone(), body.new_password.clone()).await.map_err(|e| {
+        let error_response = serde_json::json!({
+            "status": "error",
+            "message": e.to_string(),
+        });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+    })?;
+    sqlx::query(r#"UPDATE login SET password = ? WHERE id = ?"#)
+        .bind(&hashed_password)
+        .bind(user.id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+    revoke_all_sessions(&data.db, user.id).await.map_err(db_error)?;
+    AuditRecord::new(AuditEvent::PasswordChange, &client)
+        .user(user.id)
+        .record(&data.db)
+        .await;
+
+    // re-read for the new token_version
+    let user = find_user(&data, user.id).await?;
+    start_session(&data, &user, &client).await
+}
+
+// User Management Handlers ---------------------------------
+
+pub async fn user_list_handler(
+    opts: Option<Query<UserFilterOptions>>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let Query(opts) = opts.unwrap_or_default();
+
+    let limit = opts.limit.unwrap_or(20).clamp(1, 100);
+    let page = opts.page.unwrap_or(1).max(1);
+    let offset = (page - 1) * limit;
+
+    let mut count_query = QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM login");
+    push_user_filters(&mut count_query, &opts);
+    let total: i64 = count_query
+        .build_query_scalar()
+        .fetch_one(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let mut query = QueryBuilder::<MySql>::new("SELECT * FROM login");
+    push_user_filters(&mut query, &opts);
+    query.push(" ORDER BY id LIMIT ");
+    query.push_bind(limit as i64);
+    query.push(" OFFSET ");
+    query.push_bind(offset as i64);
+    let users = query
+        .build_query_as::<LoginModel>()
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let users = users
+        .iter()
+        .map(filter_user_record)
+        .collect::<Vec<FilteredUser>>();
+
+    Ok(Json(json!({
+        "status": "success",
+        "page": page,
+        "limit": limit,
+        "total": total,
+        "users": users,
+    })))
+}
+
+fn push_user_filters(query: &mut QueryBuilder<'_, MySql>, opts: &UserFilterOptions) {
+    query.push(" WHERE 1 = 1");
+    if let Some(q) = opts.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        query.push(" AND (name LIKE ");
+        query.push_bind(pattern.clone());
+        query.push(" OR email LIKE ");
+        query.push_bind(pattern);
+        query.push(")");
+    }
+    if let Some(role) = opts.role {
+        query.push(" AND role = ");
+        query.push_bind(role.as_str());
+    }
+    match opts.active {
+        Some(true) => { query.push(" AND deactivated_at IS NULL"); }
+        Some(false) => { query.push(" AND deactivated_at IS NOT NULL"); }
+        None => {}
+    }
+}
+
+pub async fn update_user_role_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(admin): Extension<LoginModel>,
+    Json(body): Json<UpdateUserRoleSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    not_self(&admin, id, "You can't change your own role")?;
+    let user = find_user(&data, id).await?;
+
+    sqlx::query(r#"UPDATE login SET role = ? WHERE id = ?"#)
+        .bind(body.role.as_str())
+        .bind(id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+    AuditRecord::new(AuditEvent::RoleChange, &client)
+        .user(id)
+        .actor(admin.id)
+        .detail(format!("{} -> {}", user.role.as_deref().unwrap_or("none"), body.role))
+        .record(&data.db)
+        .await;
+
+    user_response(&data, id).await
+}
+
+// Logs the user out everywhere; auth_guard refuses the account until it is reactivated
+pub async fn deactivate_user_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(admin): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    not_self(&admin, id, "You can't deactivate your own account")?;
+    find_user(&data, id).await?;
+
+    sqlx::query(r#"UPDATE login SET deactivated_at = ? WHERE id = ? AND deactivated_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+    revoke_all_sessions(&data.db, id).await.map_err(db_error)?;
+    AuditRecord::new(AuditEvent::AccountDeactivated, &client)
+        .user(id)
+        .actor(admin.id)
+        .record(&data.db)
+        .await;
+
+    user_response(&data, id).await
+}
+
+pub async fn reactivate_user_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(admin): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    find_user(&data, id).await?;
+
+    sqlx::query(r#"UPDATE login SET deactivated_at = NULL WHERE id = ?"#)
+        .bind(id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+    AuditRecord::new(AuditEvent::AccountReactivated, &client)
+        .user(id)
+        .actor(admin.id)
+        .record(&data.db)
+        .await;
+
+    user_response(&data, id).await
+}
+
+// Logs the user out everywhere and emails a reset link. Logging in is refused
+// until the password has been reset.
+pub async fn force_password_reset_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(admin): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    not_self(&admin, id, "You can't force a password reset on your own account")?;
+    let user = find_user(&data, id).await?;
+
+    sqlx::query(r#"UPDATE login SET password_reset_required = TRUE WHERE id = ?"#)
+        .bind(id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+    revoke_all_sessions(&data.db, id).await.map_err(db_error)?;
+    // a forced reset usually means the account may be compromised
+    revoke_all_api_tokens(&data.db, id).await.map_err(db_error)?;
+    send_password_reset(&data, &user).await.map_err(db_error)?;
+    AuditRecord::new(AuditEvent::PasswordResetForced, &client)
+        .user(id)
+        .actor(admin.id)
+        .record(&data.db)
+        .await;
+
+    user_response(&data, id).await
+}
+
+async fn find_user(data: &AppState, id: i64) -> Result<LoginModel, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as::<_, LoginModel>(r#"SELECT * FROM login WHERE id = ?"#)
+        .bind(id)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| {
+            let error_response = serde_json::json!({
+                "status": "fail",
+                "message": format!("User with ID: {} not found", id),
+            });
+            (StatusCode::NOT_FOUND, Json(error_response))
+        })
+}
+
+async fn user_response(
+    data: &AppState,
+    id: i64,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let user = find_user(data, id).await?;
+    Ok(Json(json!({
+        "status": "success",
+        "data": json!({
+            "user": filter_user_record(&user)
+        })
+    })))
+}
+
+// Keeps admins from locking themselves out
+fn not_self(admin: &LoginModel, id: i64, message: &str) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if admin.id == id {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": message,
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    Ok(())
+}
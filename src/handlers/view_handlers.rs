@@ -0,0 +1,250 @@
+use std::sync::Arc;
+use axum::{
+    extract::{rejection::QueryRejection, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json
+};
+
+use serde_json::{json, Value};
+use crate::{
+    handlers::{
+        auth_handlers::db_error,
+        ticket_handlers::{invalid_filter, list_tickets, validate_ticket_filters},
+    },
+    model::{LoginModel, Role, TicketViewModel},
+    schema::{CreateTicketViewSchema, FilterOptions, TicketFilterOptions, UpdateTicketViewSchema},
+    AppState
+};
+
+// Ticket View Handlers -------------------------------------
+
+// The caller's own views and everyone's shared ones
+pub async fn view_list_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let views = sqlx::query_as::<_, TicketViewModel>(
+        r#"SELECT * FROM ticket_views WHERE owner_id = ? OR shared = TRUE ORDER BY name"#,
+    )
+    .bind(user.id)
+    .fetch_all(&data.db)
+    .await
+    .map_err(db_error)?;
+
+    let views = views.iter().map(view_response).collect::<Vec<Value>>();
+    Ok(Json(json!(views)))
+}
+
+pub async fn create_view_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+    Json(body): Json<CreateTicketViewSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let name = view_name(&body.name)?;
+    let filters = view_filters(body.filters, &user)?;
+    let result = sqlx::query(r#"INSERT INTO ticket_views (owner_id, name, shared, filters) VALUES (?, ?, ?, ?)"#)
+        .bind(user.id)
+        .bind(name)
+        .bind(body.shared)
+        .bind(filters)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let view = find_view(&data, result.last_insert_id() as i64).await?;
+    Ok((StatusCode::CREATED, Json(view_response(&view))))
+}
+
+// Owner or admin only
+pub async fn update_view_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+    Json(body): Json<UpdateTicketViewSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let view = editable_view(&data, id, &user).await?;
+    let name = match &body.name {
+        Some(name) => view_name(name)?,
+        None => view.name.clone(),
+    };
+    let filters = match body.filters {
+        Some(filters) => view_filters(filters, &user)?,
+        None => view.filters.clone(),
+    };
+
+    sqlx::query(r#"UPDATE ticket_views SET name = ?, shared = ?, filters = ? WHERE id = ?"#)
+        .bind(name)
+        .bind(body.shared.unwrap_or(view.shared))
+        .bind(filters)
+        .bind(id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let view = find_view(&data, id).await?;
+    Ok(Json(view_response(&view)))
+}
+
+// Owner or admin only
+pub async fn delete_view_handler(
+    Path(id): Path<i64>,
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    editable_view(&data, id, &user).await?;
+    sqlx::query(r#"DELETE FROM ticket_views WHERE id = ?"#)
+        .bind(id)
+        .execute(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// The ticket list with the view's filters and sort order. `assignee=me` in a
+// shared view means whoever is looking at it.
+pub async fn view_tickets_handler(
+    Path(id): Path<i64>,
+    opts: Result<Query<FilterOptions>, QueryRejection>,
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<LoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let Query(opts) = opts.map_err(|e| invalid_filter(&e.body_text()))?;
+    let view = find_view(&data, id).await?;
+    if !view.shared && view.owner_id != user.id {
+        return Err(view_not_found(id));
+    }
+
+    let filters = page_filters(&view, &opts)?;
+    let tickets = list_tickets(&data.db, &filters, &user).await?;
+
+    Ok(Json(json!(tickets)))
+}
+
+// The stored filters with the page asked for
+fn page_filters(
+    view: &TicketViewModel,
+    opts: &FilterOptions,
+) -> Result<TicketFilterOptions, (StatusCode, Json<serde_json::Value>)> {
+    let mut filters: TicketFilterOptions = serde_json::from_str(&view.filters).map_err(|e| {
+        let error_response = serde_json::json!({
+            "status": "error",
+            "message": format!("Stored filters of view {} are invalid: {}", view.id, e),
+        });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+    })?;
+    filters.page = opts.page;
+    filters.limit = opts.limit;
+    Ok(filters)
+}
+
+fn view_response(view: &TicketViewModel) -> Value {
+    json!({
+        "id": view.id,
+        "owner_id": view.owner_id,
+        "name": view.name,
+        "shared": view.shared,
+        "filters": serde_json::from_str::<Value>(&view.filters).unwrap_or_default(),
+        "created_at": view.created_at,
+        "updated_at": view.updated_at,
+    })
+}
+
+fn view_name(name: &str) -> Result<String, (StatusCode, Json<serde_json::Value>)> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        let error_response = serde_json::json!({
+            "status": "fail",
+            "message": "A view name needs 1 to 100 characters",
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    Ok(name.to_string())
+}
+
+// Checked like a list request so a broken view can't be saved. Paging isn't
+// part of a view, it comes with each request for its tickets.
+fn view_filters(mut filters: TicketFilterOptions, user: &LoginModel) -> Result<String, (StatusCode, Json<serde_json::Value>)> {
+    filters.page = None;
+    filters.limit = None;
+    validate_ticket_filters(&filters, user)?;
+    Ok(serde_json::to_string(&filters).unwrap_or_default())
+}
+
+async fn find_view(data: &AppState, id: i64) -> Result<TicketViewModel, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as::<_, TicketViewModel>(r#"SELECT * FROM ticket_views WHERE id = ?"#)
+        .bind(id)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| view_not_found(id))
+}
+
+// Others' private views don't exist as far as the caller can tell; shared views
+// can be seen but only changed by their owner or an admin
+async fn editable_view(
+    data: &AppState,
+    id: i64,
+    user: &LoginModel,
+) -> Result<TicketViewModel, (StatusCode, Json<serde_json::Value>)> {
+    let view = find_view(data, id).await?;
+    if view.owner_id == user.id || user.role() == Some(Role::Admin) {
+        return Ok(view);
+    }
+    if !view.shared {
+        return Err(view_not_found(id));
+    }
+    let error_response = serde_json::json!({
+        "status": "fail",
+        "message": "Only the owner of a shared view can change it",
+    });
+    Err((StatusCode::FORBIDDEN, Json(error_response)))
+}
+
+fn view_not_found(id: i64) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = serde_json::json!({
+        "status": "fail",
+        "message": format!("View with ID: {} not found", id)
+    });
+    (StatusCode::NOT_FOUND, Json(error_response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::ticket_handlers::ticket_page;
+
+    fn view(filters: &str) -> TicketViewModel {
+        TicketViewModel {
+            id: 1,
+            owner_id: 1,
+            name: "Open tickets".to_string(),
+            shared: true,
+            filters: filters.to_string(),
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn view_pages_are_clamped_like_the_ticket_list() {
+        let opts = FilterOptions { page: Some(0), limit: Some(100_000) };
+        let filters = page_filters(&view(r#"{"status":"created","sort":"-priority"}"#), &opts).unwrap();
+        assert_eq!(filters.status.as_deref(), Some("created"));
+        assert_eq!(ticket_page(&filters), (100, 0));
+    }
+
+    #[test]
+    fn view_page_comes_from_the_request_not_the_stored_filters() {
+        let opts = FilterOptions { page: Some(3), limit: None };
+        let filters = page_filters(&view(r#"{"page":7,"limit":5}"#), &opts).unwrap();
+        assert_eq!(ticket_page(&filters), (20, 40));
+    }
+
+    #[test]
+    fn broken_stored_filters_are_a_server_error() {
+        let result = page_filters(&view("not json"), &FilterOptions::default());
+        assert_eq!(result.unwrap_err().0, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
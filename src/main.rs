@@ -10,12 +10,20 @@ use route::create_router;
 use std::sync::Arc;
 use dotenv::dotenv;
 use config::Config;
+use utils::mailer::{mailer_from_config, Mailer};
+use utils::throttle::LoginThrottle;
+use utils::keys::JwtKeys;
+use utils::csrf::CSRF_HEADER;
+use utils::password::{hasher_from_config, PasswordHasher};
+use utils::oidc::OidcClient;
+use utils::password_policy::PasswordPolicy;
+use std::net::SocketAddr;
 
 use axum::{
     routing::{get, post},
     http::{StatusCode,
         header::{ACCEPT,AUTHORIZATION, CONTENT_TYPE},
-        HeaderValue, Method},
+        HeaderName, HeaderValue, Method},
     extract::{Path, Query},
     Json, Router,
     response::{IntoResponse,Html}
@@ -25,6 +33,13 @@ use sqlx::mysql::{MySqlPool, MySqlPoolOptions};
 pub struct AppState {
     db: MySqlPool,
     env: Config,
+    mailer: Arc<dyn Mailer>,
+    login_throttle: LoginThrottle,
+    keys: JwtKeys,
+    password_hasher: Arc<dyn PasswordHasher>,
+    password_policy: PasswordPolicy,
+    // None when single sign-on isn't configured
+    oidc: Option<OidcClient>,
 }
 
 #[tokio::main]
@@ -51,11 +66,18 @@ async fn main() {
         .allow_origin(origin_url.parse::<HeaderValue>().unwrap())
         .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
         .allow_credentials(true)
-        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
+        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE, HeaderName::from_static(CSRF_HEADER)]);
 
-    let app = create_router(Arc::new(AppState {db:pool.clone(), env: config.clone(), })).layer(cors);
+    let mailer = mailer_from_config(&config);
+    let login_throttle = LoginThrottle::new(&config);
+    let keys = JwtKeys::from_config(&config);
+    let password_hasher = hasher_from_config(&config);
+    let password_policy = PasswordPolicy::from_config(&config);
+    let oidc = OidcClient::from_config(&config);
+    let app = create_router(Arc::new(AppState {db:pool.clone(), env: config.clone(), mailer, login_throttle, keys, password_hasher, password_policy, oidc, })).layer(cors);
 
     println!("🚀 Server started successfully");
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap()
+    // ConnectInfo gives handlers the client address (login throttling)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap()
 }
\ No newline at end of file
@@ -29,6 +29,8 @@ pub struct FilteredUser {
     pub name: String,
     pub email: String,
     pub role: String,
+    pub email_verified: bool,
+    pub active: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -36,8 +38,214 @@ pub struct TokenClaims {
     pub sub: String,
     pub exp: usize, // (validate_exp defaults to true in validation). Expiration time (as UTC timestamp)
     pub iat: usize, // Issued at (as UTC timestamp)
+    pub jti: String, // Unique token id, checked against the revoked_tokens table
+    pub ver: i32, // login.token_version at the time the token was issued
+    // sessions.id of the login the token belongs to. Missing in tokens issued
+    // before sessions were tracked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
 }
 
+// Short-lived token handed out after the password step of a 2FA login. Its
+// `aud` makes auth_guard reject it, so it is only good for /api/login/2fa.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MfaClaims {
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub aud: String,
+}
+
+// State of a single sign-on attempt, kept in a signed cookie between the
+// redirect to the provider and the callback
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OidcFlowClaims {
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    // set when a logged in user links single sign-on to their account
+    #[serde(default)]
+    pub link_user_id: Option<i64>,
+    pub exp: usize,
+    pub iat: usize,
+    pub aud: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Agent,
+    User,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Agent => "agent",
+            Role::User => "user",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "admin" => Ok(Role::Admin),
+            "agent" => Ok(Role::Agent),
+            "user" => Ok(Role::User),
+            other => Err(format!("Unknown role: {}", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Where a ticket is in the workflow:
+// created -> in_progress -> resolved -> closed, resolved and closed tickets can be reopened
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketStatus {
+    Created,
+    InProgress,
+    Resolved,
+    Closed,
+}
+
+impl TicketStatus {
+    pub const ALL: [TicketStatus; 4] = [
+        TicketStatus::Created,
+        TicketStatus::InProgress,
+        TicketStatus::Resolved,
+        TicketStatus::Closed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TicketStatus::Created => "created",
+            TicketStatus::InProgress => "in_progress",
+            TicketStatus::Resolved => "resolved",
+            TicketStatus::Closed => "closed",
+        }
+    }
+
+    // The states a ticket in this state may move to
+    pub fn next_states(&self) -> &'static [TicketStatus] {
+        match self {
+            TicketStatus::Created => &[TicketStatus::InProgress],
+            TicketStatus::InProgress => &[TicketStatus::Resolved],
+            TicketStatus::Resolved => &[TicketStatus::Closed, TicketStatus::InProgress],
+            TicketStatus::Closed => &[TicketStatus::InProgress],
+        }
+    }
+
+    // Staying in the same state is always fine
+    pub fn can_move_to(&self, next: TicketStatus) -> bool {
+        *self == next || self.next_states().contains(&next)
+    }
+}
+
+impl std::str::FromStr for TicketStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TicketStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("Unknown ticket status: {}", s))
+    }
+}
+
+impl std::fmt::Display for TicketStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketPriority {
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl TicketPriority {
+    // lowest first
+    pub const ALL: [TicketPriority; 4] = [
+        TicketPriority::Low,
+        TicketPriority::Medium,
+        TicketPriority::High,
+        TicketPriority::Urgent,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TicketPriority::Low => "low",
+            TicketPriority::Medium => "medium",
+            TicketPriority::High => "high",
+            TicketPriority::Urgent => "urgent",
+        }
+    }
+}
+
+impl std::str::FromStr for TicketPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TicketPriority::ALL
+            .into_iter()
+            .find(|priority| priority.as_str() == s)
+            .ok_or_else(|| format!("Unknown ticket priority: {}", s))
+    }
+}
+
+impl std::fmt::Display for TicketPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Stored as their as_str() name in VARCHAR columns. sqlx's derive expects a
+// MySQL ENUM column, so these go through &str by hand.
+macro_rules! varchar_enum {
+    ($name:ident) => {
+        impl sqlx::Type<sqlx::MySql> for $name {
+            fn type_info() -> sqlx::mysql::MySqlTypeInfo {
+                <str as sqlx::Type<sqlx::MySql>>::type_info()
+            }
+
+            fn compatible(ty: &sqlx::mysql::MySqlTypeInfo) -> bool {
+                <str as sqlx::Type<sqlx::MySql>>::compatible(ty)
+            }
+        }
+
+        impl<'q> sqlx::Encode<'q, sqlx::MySql> for $name {
+            fn encode_by_ref(&self, buf: &mut Vec<u8>) -> sqlx::encode::IsNull {
+                <&str as sqlx::Encode<'q, sqlx::MySql>>::encode(self.as_str(), buf)
+            }
+        }
+
+        impl<'r> sqlx::Decode<'r, sqlx::MySql> for $name {
+            fn decode(value: sqlx::mysql::MySqlValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+                let value = <&str as sqlx::Decode<'r, sqlx::MySql>>::decode(value)?;
+                Ok(value.parse::<$name>()?)
+            }
+        }
+    };
+}
+
+varchar_enum!(TicketStatus);
+varchar_enum!(TicketPriority);
+
 #[derive(Clone, Debug, Deserialize, Serialize, sqlx::FromRow)]
 #[allow(non_snake_case)]
 pub struct LoginModel {
@@ -46,7 +254,22 @@ pub struct LoginModel {
     pub email: Option<String>,
     pub password: Option<String>,
     pub role: Option<String>,
+    pub token_version: i32,
+    pub email_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub totp_last_step: i64,
+    pub deactivated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub password_reset_required: bool,
+    // `sub` of the single sign-on identity linked to the account
+    pub oidc_subject: Option<String>,
+}
 
+impl LoginModel {
+    // None when the row has no role or one we don't know about
+    pub fn role(&self) -> Option<Role> {
+        self.role.as_deref().and_then(|role| role.parse().ok())
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow)]
@@ -64,10 +287,16 @@ pub struct TicketModel {
     pub id: i64,
     // pub title: String,
     pub summary: String,
-    pub priority: String,
-    pub status: String,
+    pub priority: TicketPriority,
+    pub status: TicketStatus,
     pub create_date: Option<chrono::DateTime<chrono::Utc>>,
     pub update_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub assignee_id: Option<i64>,
+    // login.name of the assignee, joined in by TICKET_SELECT
+    pub assignee_name: Option<String>,
+    // tag names sorted by name, filled in by load_tags
+    #[sqlx(skip)]
+    pub tags: Vec<String>,
 }
 
 // the output to our handler
@@ -77,8 +306,188 @@ pub struct TicketModelResponse {
     pub id: i64,
     // pub title: String,
     pub summary: String,
-    pub priority: String,
-    pub status: String,
+    pub priority: TicketPriority,
+    pub status: TicketStatus,
     pub createdAt: chrono::DateTime<chrono::Utc>,
     pub updatedAt: chrono::DateTime<chrono::Utc>,
+    pub assigneeId: Option<i64>,
+    pub assigneeName: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TicketViewModel {
+    pub id: i64,
+    pub owner_id: i64,
+    pub name: String,
+    pub shared: bool,
+    // TicketFilterOptions as JSON
+    pub filters: String,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct TagModel {
+    pub id: i64,
+    pub name: String,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RefreshTokenModel {
+    pub id: i64,
+    pub user_id: i64,
+    pub family_id: String,
+    pub token_hash: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PasswordResetModel {
+    pub id: i64,
+    pub user_id: i64,
+    pub token_hash: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct EmailVerificationModel {
+    pub id: i64,
+    pub user_id: i64,
+    pub token_hash: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    // new address for an email change, None for the sign-up verification
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ApiTokenModel {
+    pub id: i64,
+    #[serde(skip_serializing)]
+    pub user_id: i64,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub scopes: String,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct InvitationModel {
+    pub id: i64,
+    pub email: String,
+    pub role: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub invited_by: Option<i64>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub accepted_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SessionModel {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub user_id: i64,
+    pub ip: String,
+    pub user_agent: String,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_seen_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    // whether this is the session making the request, filled in by the handler
+    #[sqlx(skip)]
+    pub current: bool,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AuthEventModel {
+    pub id: i64,
+    pub event: String,
+    pub user_id: Option<i64>,
+    pub actor_id: Option<i64>,
+    pub email: Option<String>,
+    pub ip: String,
+    pub user_agent: String,
+    pub detail: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+// How the current request was authenticated. auth_guard puts it in the request
+// extensions next to the LoginModel.
+#[derive(Debug, Clone)]
+pub enum AuthMethod {
+    // JWT from a login, may do anything the user's role allows
+    Session,
+    // personal access token, limited to its scopes
+    ApiToken { id: i64, scopes: Vec<String> },
+}
+
+impl AuthMethod {
+    pub fn allows(&self, scope: &str) -> bool {
+        match self {
+            AuthMethod::Session => true,
+            AuthMethod::ApiToken { scopes, .. } => scopes.iter().any(|s| s == scope),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tickets_move_forward_one_state_at_a_time() {
+        assert!(TicketStatus::Created.can_move_to(TicketStatus::InProgress));
+        assert!(TicketStatus::InProgress.can_move_to(TicketStatus::Resolved));
+        assert!(TicketStatus::Resolved.can_move_to(TicketStatus::Closed));
+
+        assert!(!TicketStatus::Created.can_move_to(TicketStatus::Resolved));
+        assert!(!TicketStatus::Created.can_move_to(TicketStatus::Closed));
+        assert!(!TicketStatus::InProgress.can_move_to(TicketStatus::Closed));
+        assert!(!TicketStatus::InProgress.can_move_to(TicketStatus::Created));
+    }
+
+    #[test]
+    fn resolved_and_closed_tickets_can_be_reopened() {
+        assert!(TicketStatus::Resolved.can_move_to(TicketStatus::InProgress));
+        assert!(TicketStatus::Closed.can_move_to(TicketStatus::InProgress));
+        assert!(!TicketStatus::Closed.can_move_to(TicketStatus::Resolved));
+        assert!(!TicketStatus::Closed.can_move_to(TicketStatus::Created));
+    }
+
+    #[test]
+    fn tickets_can_stay_in_their_state() {
+        for status in TicketStatus::ALL {
+            assert!(status.can_move_to(status));
+            assert!(!status.next_states().contains(&status));
+        }
+    }
+
+    #[test]
+    fn status_and_priority_names_round_trip() {
+        for status in TicketStatus::ALL {
+            assert_eq!(status.as_str().parse::<TicketStatus>(), Ok(status));
+            assert_eq!(serde_json::to_value(status).unwrap(), status.as_str());
+        }
+        for priority in TicketPriority::ALL {
+            assert_eq!(priority.as_str().parse::<TicketPriority>(), Ok(priority));
+            assert_eq!(serde_json::to_value(priority).unwrap(), priority.as_str());
+        }
+        assert!("In Progress".parse::<TicketStatus>().is_err());
+        assert!("urgnt".parse::<TicketPriority>().is_err());
+        assert!(serde_json::from_str::<TicketPriority>("\"High\"").is_err());
+    }
 }
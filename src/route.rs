@@ -2,35 +2,225 @@ use std::sync::Arc;
 
 use axum::{
     middleware,
-    routing::{get, post, put},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use crate::{
     handlers::{
+        audit_handlers::audit_list_handler,
+        invitation_handlers::{create_invitation_handler, invitation_list_handler, revoke_invitation_handler},
+        oidc_handlers::{oidc_callback_handler, oidc_link_handler, oidc_login_handler},
+        tag_handlers::{add_ticket_tags_handler, create_tag_handler, delete_tag_handler, remove_ticket_tag_handler, rename_tag_handler, tag_list_handler},
+        view_handlers::{create_view_handler, delete_view_handler, update_view_handler, view_list_handler, view_tickets_handler},
+        session_handlers::{revoke_session_handler, revoke_user_session_handler, session_list_handler, user_session_list_handler},
         auth_handlers::{
-         get_me_handler, login_handler, logout_handler, refresh_token_handler, register_handler}, comment_handlers::{comments_list_handler, create_comment_handler}, ticket_handlers::{create_ticket_handler, delete_ticket_handler, edit_ticket_handler, get_ticket_handler, health_checker_handler, ticket_list_handler}
+         csrf_token_handler, forgot_password_handler, get_me_handler, jwks_handler, login_handler, logout_all_handler, mfa_login_handler, logout_handler, refresh_token_handler, register_handler, resend_verification_handler, reset_password_handler, verify_email_handler}, comment_handlers::{comments_list_handler, create_comment_handler}, ticket_handlers::{assign_ticket_handler, create_ticket_handler, delete_ticket_handler, edit_ticket_handler, get_ticket_handler, health_checker_handler, reassign_ticket_handler, ticket_list_handler, unassign_ticket_handler}, token_handlers::{api_token_list_handler, create_api_token_handler, revoke_api_token_handler}, user_handlers::{change_password_handler, deactivate_user_handler, force_password_reset_handler, reactivate_user_handler, update_me_handler, update_user_role_handler, user_list_handler}, totp_handlers::{totp_confirm_handler, totp_disable_handler, totp_enroll_handler}
+    },
+    model::Role,
+    utils::{
+        api_token::{COMMENTS_READ, COMMENTS_WRITE, TICKETS_READ, TICKETS_WRITE},
+        csrf::require_csrf,
+        guard::{auth_guard, require_role, require_scope, require_session, require_verified},
     },
-    utils::guard::auth_guard,
     AppState,
 };
 
 pub fn create_router(app_state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/api/users/me",get(get_me_handler)
-                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth_guard)),
+    // Routes anyone can call without a token
+    let public = Router::new()
+        .route("/api/refresh", post(refresh_token_handler)
+            .route_layer(middleware::from_fn(require_csrf)),
+        )
+        .route("/api/csrf", get(csrf_token_handler))
+        // POST with the CSRF header, or any page could log users out with a link
+        .route("/api/logout", post(logout_handler)
+            .route_layer(middleware::from_fn(require_csrf)),
         )
-        .route("/api/refresh", post(refresh_token_handler))
-        .route("/api/logout", get(logout_handler))
         .route("/api/register", post(register_handler))
+        .route("/api/password/forgot", post(forgot_password_handler))
+        .route("/api/password/reset", post(reset_password_handler))
+        .route("/api/verify", post(verify_email_handler))
+        .route("/api/verify/resend", post(resend_verification_handler))
         .route("/api/login", post(login_handler))
+        .route("/api/login/2fa", post(mfa_login_handler))
+        .route("/api/oidc/login", get(oidc_login_handler))
+        .route("/api/oidc/callback", get(oidc_callback_handler))
         .route("/api/healthchecker", get(health_checker_handler))
-        .route("/api/ticket/all", get(ticket_list_handler))
-        .route("/api/ticket/", post(create_ticket_handler))
+        .route("/.well-known/jwks.json", get(jwks_handler));
+
+    // Every route below goes through auth_guard and declares the roles allowed to call it.
+    // Cookie-authenticated requests that change something also need the CSRF header.
+    // These ones stay reachable for accounts that haven't verified their email yet.
+    let account = Router::new()
+        .route("/api/users/me", get(get_me_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User])),
+        )
+        .route("/api/users/me", patch(update_me_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(middleware::from_fn(require_session)),
+        )
+        .route("/api/users/me/password", post(change_password_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(middleware::from_fn(require_session)),
+        )
+        .route("/api/logout/all", post(logout_all_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(middleware::from_fn(require_session)),
+        )
+        .route("/api/sessions", get(session_list_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(middleware::from_fn(require_session)),
+        )
+        .route("/api/sessions/:id", delete(revoke_session_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(middleware::from_fn(require_session)),
+        )
+        .route("/api/oidc/link", post(oidc_link_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(middleware::from_fn(require_session)),
+        )
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth_guard))
+        .route_layer(middleware::from_fn(require_csrf));
+
+    // Account management, API tokens can't call these
+    let session_only = Router::new()
+        .route("/api/2fa/enroll", post(totp_enroll_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent])),
+        )
+        .route("/api/2fa/confirm", post(totp_confirm_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent])),
+        )
+        .route("/api/2fa/disable", post(totp_disable_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent])),
+        )
+        .route("/api/tokens", get(api_token_list_handler)
+            .post(create_api_token_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User])),
+        )
+        .route("/api/tokens/:id", delete(revoke_api_token_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User])),
+        )
+        .route("/api/users", get(user_list_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/users/:id/role", patch(update_user_role_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/users/:id/deactivate", post(deactivate_user_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/users/:id/reactivate", post(reactivate_user_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/users/:id/password-reset", post(force_password_reset_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/users/:id/sessions", get(user_session_list_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/users/:id/sessions/:session_id", delete(revoke_user_session_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/audit", get(audit_list_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/invitations", get(invitation_list_handler)
+            .post(create_invitation_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/invitations/:id", delete(revoke_invitation_handler)
+            .route_layer(require_role(&[Role::Admin])),
+        )
+        .route("/api/views", post(create_view_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User])),
+        )
+        .route("/api/views/:id", patch(update_view_handler)
+            .delete(delete_view_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User])),
+        )
+        .route_layer(middleware::from_fn(require_session));
+
+    // API tokens additionally need the scope named by require_scope
+    let protected = Router::new()
+        .route("/api/ticket/all", get(ticket_list_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(require_scope(TICKETS_READ)),
+        )
+        .route("/api/ticket/", post(create_ticket_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
         .route("/api/ticket/:id", get(get_ticket_handler)
-            .patch(edit_ticket_handler)
-            .delete(delete_ticket_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(require_scope(TICKETS_READ)),
+        )
+        .route("/api/ticket/:id", patch(edit_ticket_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
+        .route("/api/ticket/:id", delete(delete_ticket_handler)
+            .route_layer(require_role(&[Role::Admin]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
+        .route("/api/ticket/:id/assign", post(assign_ticket_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
+        .route("/api/ticket/:id/reassign", post(reassign_ticket_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
+        .route("/api/ticket/:id/unassign", post(unassign_ticket_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent]))
+            .route_layer(require_scope(TICKETS_WRITE)),
         )
-        .route("/api/comments/:id", get(comments_list_handler))
-        .route("/api/comments/", post(create_comment_handler))
+        .route("/api/ticket/:id/tags", post(add_ticket_tags_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
+        .route("/api/ticket/:id/tags/:tag_id", delete(remove_ticket_tag_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
+        .route("/api/views", get(view_list_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(require_scope(TICKETS_READ)),
+        )
+        .route("/api/views/:id/tickets", get(view_tickets_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(require_scope(TICKETS_READ)),
+        )
+        .route("/api/tags", get(tag_list_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(require_scope(TICKETS_READ)),
+        )
+        .route("/api/tags", post(create_tag_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
+        .route("/api/tags/:id", patch(rename_tag_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
+        .route("/api/tags/:id", delete(delete_tag_handler)
+            .route_layer(require_role(&[Role::Admin]))
+            .route_layer(require_scope(TICKETS_WRITE)),
+        )
+        .route("/api/comments/:id", get(comments_list_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(require_scope(COMMENTS_READ)),
+        )
+        .route("/api/comments/", post(create_comment_handler)
+            .route_layer(require_role(&[Role::Admin, Role::Agent, Role::User]))
+            .route_layer(require_scope(COMMENTS_WRITE)),
+        )
+        .merge(session_only)
+        .route_layer(middleware::from_fn(require_verified))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth_guard))
+        .route_layer(middleware::from_fn(require_csrf));
+
+    public
+        .merge(account)
+        .merge(protected)
         .with_state(app_state)
 }
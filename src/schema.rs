@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::model::{Role, TicketPriority, TicketStatus};
+
 // Structs that will be used to deserialize the request 
 // parameters and bodies in the Axum route functions and also
 // ensure that the required fields are included in the JSON object.
@@ -10,7 +12,6 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CreateCommentSchema {
     pub content: String,
-    pub author_id: i64,
     pub ticket_id: i64,
 }
 
@@ -19,6 +20,14 @@ pub struct RegisterSchema {
     pub email: String,
     pub password: String,
     pub name: String,
+    // token from an invitation email, required when REGISTRATION=invite
+    pub invitation: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateInvitationSchema {
+    pub email: String,
+    pub role: Role,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -27,26 +36,174 @@ pub struct LoginSchema {
     pub password: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ForgotPasswordSchema {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResetPasswordSchema {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VerifyEmailSchema {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResendVerificationSchema {
+    pub email: String,
+}
+
+// Fields left out stay unchanged. A new email only replaces the current one
+// once it has been verified.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateProfileSchema {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChangePasswordSchema {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TotpCodeSchema {
+    pub code: String,
+}
+
+// Second step of a 2FA login: either a TOTP code or one of the recovery codes
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MfaLoginSchema {
+    pub mfa_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateApiTokenSchema {
+    pub name: String,
+    pub scopes: Vec<String>,
+    // the token never expires when left out
+    pub expires_in_days: Option<i64>,
+}
+
+// Query string the provider redirects back with after a single sign-on login
+#[derive(Deserialize, Debug)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
 #[derive(Deserialize, Debug, Default)]
 pub struct FilterOptions {
     pub page: Option<usize>,
     pub limit: Option<usize>,
 }
 
+// Ticket list filters: `assignee` is a user id, `me` or `none`. `status`,
+// `priority` and `tag` take comma separated lists, `tag_match` is `any`
+// (default) or `all`. `q` searches the summary, `sort` looks like
+// `-priority,created_at` (`-` for descending).
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TicketFilterOptions {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    pub assignee: Option<String>,
+    pub tag: Option<String>,
+    pub tag_match: Option<String>,
+    pub status: Option<String>,
+    pub priority: Option<String>,
+    pub q: Option<String>,
+    pub created_from: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_to: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_from: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_to: Option<chrono::DateTime<chrono::Utc>>,
+    pub sort: Option<String>,
+}
+
+// Admin user search: `q` matches name or email, `active=false` lists deactivated accounts
+#[derive(Deserialize, Debug, Default)]
+pub struct UserFilterOptions {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    pub q: Option<String>,
+    pub role: Option<Role>,
+    pub active: Option<bool>,
+}
+
+// Audit log query, `from` and `to` are RFC 3339 timestamps
+#[derive(Deserialize, Debug, Default)]
+pub struct AuditFilterOptions {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    pub user_id: Option<i64>,
+    pub event: Option<String>,
+    pub ip: Option<String>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateUserRoleSchema {
+    pub role: Role,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
+// New tickets always start out `created`
 pub struct CreateTicketSchema {
     pub summary: String,
-    // #[serde(skip_serializing_if = "Option::is_none")]
-    pub priority: String,
-    // #[serde(skip_serializing_if = "Option::is_none")]
-    pub status: String,
+    pub priority: TicketPriority,
+}
+
+// `assignee_id` defaults to the caller when assigning
+#[derive(Deserialize, Debug)]
+pub struct AssignTicketSchema {
+    pub assignee_id: Option<i64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReassignTicketSchema {
+    pub assignee_id: i64,
+}
+
+// `filters` takes the ticket list query parameters, page and limit excepted
+#[derive(Deserialize, Debug)]
+pub struct CreateTicketViewSchema {
+    pub name: String,
+    #[serde(default)]
+    pub shared: bool,
+    pub filters: TicketFilterOptions,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateTicketViewSchema {
+    pub name: Option<String>,
+    pub shared: Option<bool>,
+    pub filters: Option<TicketFilterOptions>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TagSchema {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TicketTagsSchema {
+    pub tag_ids: Vec<i64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UpdateTicketSchema {
     pub summary: Option<String>,
-    pub priority: Option<String>,
-    pub status: Option<String>,
+    pub priority: Option<TicketPriority>,
+    pub status: Option<TicketStatus>,
 }
 
 // #[derive(Deserialize, Debug)]
@@ -0,0 +1,76 @@
+use chrono::{Duration, Utc};
+use sqlx::MySqlPool;
+
+use crate::model::ApiTokenModel;
+
+use super::token::{generate_token, hash_token};
+
+// Lets auth_guard tell API tokens and JWTs apart
+pub const API_TOKEN_PREFIX: &str = "tkt_";
+
+pub const TICKETS_READ: &str = "tickets:read";
+pub const TICKETS_WRITE: &str = "tickets:write";
+pub const COMMENTS_READ: &str = "comments:read";
+pub const COMMENTS_WRITE: &str = "comments:write";
+
+pub const SCOPES: &[&str] = &[TICKETS_READ, TICKETS_WRITE, COMMENTS_READ, COMMENTS_WRITE];
+
+// Stores a new token and returns the raw value, which is never shown again
+pub async fn create_api_token(
+    db: &MySqlPool,
+    user_id: i64,
+    name: &str,
+    scopes: &[String],
+    expires_in_days: Option<i64>,
+) -> Result<(i64, String), sqlx::Error> {
+    let token = format!("{}{}", API_TOKEN_PREFIX, generate_token());
+    let expires_at = expires_in_days.map(|days| Utc::now() + Duration::days(days));
+
+    let result = sqlx::query(
+        r#"INSERT INTO api_tokens (user_id, name, token_hash, scopes, expires_at) VALUES (?, ?, ?, ?, ?)"#,
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(hash_token(&token))
+    .bind(scopes.join(" "))
+    .bind(expires_at)
+    .execute(db)
+    .await?;
+
+    Ok((result.last_insert_id() as i64, token))
+}
+
+// Looks up a token that is neither revoked nor expired and records its use
+pub async fn authenticate_api_token(
+    db: &MySqlPool,
+    token: &str,
+) -> Result<Option<ApiTokenModel>, sqlx::Error> {
+    let now = Utc::now();
+    let api_token = sqlx::query_as::<_, ApiTokenModel>(
+        r#"SELECT * FROM api_tokens WHERE token_hash = ? AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > ?)"#,
+    )
+    .bind(hash_token(token))
+    .bind(now)
+    .fetch_optional(db)
+    .await?;
+
+    if let Some(api_token) = &api_token {
+        sqlx::query(r#"UPDATE api_tokens SET last_used_at = ? WHERE id = ?"#)
+            .bind(now)
+            .bind(api_token.id)
+            .execute(db)
+            .await?;
+    }
+
+    Ok(api_token)
+}
+
+// Every live token of the user stops working, e.g. when an admin forces a password reset
+pub async fn revoke_all_api_tokens(db: &MySqlPool, user_id: i64) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(r#"UPDATE api_tokens SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,141 @@
+use std::{convert::Infallible, net::SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts},
+};
+use sqlx::MySqlPool;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditEvent {
+    LoginSuccess,
+    LoginFailure,
+    Logout,
+    LogoutAll,
+    Refresh,
+    RefreshReuse,
+    PasswordChange,
+    PasswordReset,
+    PasswordResetForced,
+    RoleChange,
+    AccountDeactivated,
+    AccountReactivated,
+    SessionRevoked,
+    OidcLinked,
+}
+
+impl AuditEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEvent::LoginSuccess => "login_success",
+            AuditEvent::LoginFailure => "login_failure",
+            AuditEvent::Logout => "logout",
+            AuditEvent::LogoutAll => "logout_all",
+            AuditEvent::Refresh => "refresh",
+            AuditEvent::RefreshReuse => "refresh_reuse",
+            AuditEvent::PasswordChange => "password_change",
+            AuditEvent::PasswordReset => "password_reset",
+            AuditEvent::PasswordResetForced => "password_reset_forced",
+            AuditEvent::RoleChange => "role_change",
+            AuditEvent::AccountDeactivated => "account_deactivated",
+            AuditEvent::AccountReactivated => "account_reactivated",
+            AuditEvent::SessionRevoked => "session_revoked",
+            AuditEvent::OidcLinked => "oidc_linked",
+        }
+    }
+}
+
+// Where a request came from, for the audit log. Needs the server to be started
+// with into_make_service_with_connect_info.
+#[derive(Clone, Debug)]
+pub struct ClientInfo {
+    pub ip: String,
+    pub user_agent: String,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_default();
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        Ok(ClientInfo { ip, user_agent: truncate(user_agent, 512) })
+    }
+}
+
+// Cut to the column width, a value too long for it would make the insert fail
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+// One row of the audit log. Only `event` and the client are required.
+pub struct AuditRecord<'a> {
+    pub event: AuditEvent,
+    pub client: &'a ClientInfo,
+    pub user_id: Option<i64>,
+    pub actor_id: Option<i64>,
+    pub email: Option<&'a str>,
+    pub detail: Option<String>,
+}
+
+impl<'a> AuditRecord<'a> {
+    pub fn new(event: AuditEvent, client: &'a ClientInfo) -> Self {
+        AuditRecord {
+            event,
+            client,
+            user_id: None,
+            actor_id: None,
+            email: None,
+            detail: None,
+        }
+    }
+
+    pub fn user(mut self, user_id: i64) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn actor(mut self, actor_id: i64) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn email(mut self, email: &'a str) -> Self {
+        self.email = Some(email);
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    // A failed write is printed, it never fails the request being audited
+    pub async fn record(self, db: &MySqlPool) {
+        let result = sqlx::query(
+            r#"INSERT INTO auth_events (event, user_id, actor_id, email, ip, user_agent, detail) VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(self.event.as_str())
+        .bind(self.user_id)
+        .bind(self.actor_id)
+        .bind(self.email.map(|email| truncate(email, 255)))
+        .bind(&self.client.ip)
+        .bind(&self.client.user_agent)
+        .bind(self.detail.as_deref().map(|detail| truncate(detail, 255)))
+        .execute(db)
+        .await;
+        if let Err(e) = result {
+            println!("🔥 Failed to write audit event {}: {}", self.event.as_str(), e);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use axum_extra::extract::cookie::{Cookie, SameSite};
+
+use crate::config::Config;
+
+use super::csrf::CSRF_COOKIE;
+
+pub const ACCESS_COOKIE: &str = "token";
+pub const REFRESH_COOKIE: &str = "refresh_token";
+pub const OIDC_FLOW_COOKIE: &str = "oidc_flow";
+
+// Parses COOKIE_SAME_SITE, case insensitive
+pub fn parse_same_site(value: &str) -> Option<SameSite> {
+    match value.to_ascii_lowercase().as_str() {
+        "strict" => Some(SameSite::Strict),
+        "lax" => Some(SameSite::Lax),
+        "none" => Some(SameSite::None),
+        _ => None,
+    }
+}
+
+fn build_cookie(config: &Config, name: &'static str, value: String, path: &'static str, max_age: time::Duration) -> Cookie<'static> {
+    let mut cookie = Cookie::build((name, value))
+        .path(path)
+        .max_age(max_age)
+        .same_site(config.cookie_same_site)
+        .secure(config.cookie_secure)
+        .http_only(true);
+    if let Some(domain) = &config.cookie_domain {
+        cookie = cookie.domain(domain.clone());
+    }
+    cookie.build()
+}
+
+// The access token, readable by every route
+pub fn access_cookie(config: &Config, token: String) -> Cookie<'static> {
+    build_cookie(config, ACCESS_COOKIE, token, "/", time::Duration::minutes(config.jwt_maxage as i64))
+}
+
+// The refresh token, only sent to the API
+pub fn refresh_cookie(config: &Config, token: String) -> Cookie<'static> {
+    build_cookie(config, REFRESH_COOKIE, token, "/api", time::Duration::days(config.refresh_token_maxage))
+}
+
+// The CSRF token, lives as long as the refresh token. Not httpOnly: the
+// frontend copies it into the X-CSRF-Token header.
+pub fn csrf_cookie(config: &Config, token: String) -> Cookie<'static> {
+    let mut cookie = build_cookie(config, CSRF_COOKIE, token, "/", time::Duration::days(config.refresh_token_maxage));
+    cookie.set_http_only(false);
+    cookie
+}
+
+// State of a single sign-on attempt, only sent to the callback. Always Lax:
+// the callback is a cross-site redirect from the provider, Strict would drop it.
+pub fn oidc_flow_cookie(config: &Config, value: String, max_age: time::Duration) -> Cookie<'static> {
+    let mut cookie = build_cookie(config, OIDC_FLOW_COOKIE, value, "/api/oidc", max_age);
+    if config.cookie_same_site == SameSite::Strict {
+        cookie.set_same_site(SameSite::Lax);
+    }
+    cookie
+}
+
+pub fn oidc_flow_removal_cookie(config: &Config) -> Cookie<'static> {
+    oidc_flow_cookie(config, String::new(), time::Duration::hours(-1))
+}
+
+// Expired copies of the cookies, to log the browser out. Path and domain have
+// to match the cookies being removed.
+pub fn removal_cookies(config: &Config) -> [Cookie<'static>; 3] {
+    [
+        build_cookie(config, ACCESS_COOKIE, String::new(), "/", time::Duration::hours(-1)),
+        build_cookie(config, REFRESH_COOKIE, String::new(), "/api", time::Duration::hours(-1)),
+        build_cookie(config, CSRF_COOKIE, String::new(), "/", time::Duration::hours(-1)),
+    ]
+}
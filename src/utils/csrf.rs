@@ -0,0 +1,59 @@
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::cookie::CookieJar;
+
+use super::{
+    cookies::{ACCESS_COOKIE, REFRESH_COOKIE},
+    guard::ErrorResponse,
+    token::generate_token,
+};
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+pub fn generate_csrf_token() -> String {
+    generate_token()
+}
+
+// Double-submit check for requests authenticated by cookie: the browser sends
+// the cookies on its own, but only our frontend can read the csrf_token cookie
+// (or the login response) and copy it into the X-CSRF-Token header.
+// Safe methods and requests without our cookies (Bearer tokens) pass untouched.
+pub async fn require_csrf(
+    cookie_jar: CookieJar,
+    req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let safe_method = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let cookie_authenticated = cookie_jar.get(ACCESS_COOKIE).is_some() || cookie_jar.get(REFRESH_COOKIE).is_some();
+    if safe_method || !cookie_authenticated {
+        return Ok(next.run(req).await);
+    }
+
+    let header_token = req
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let cookie_token = cookie_jar
+        .get(CSRF_COOKIE)
+        .map(|cookie| cookie.value())
+        .unwrap_or_default();
+    if cookie_token.is_empty() || !constant_time_eq(header_token.as_bytes(), cookie_token.as_bytes()) {
+        let json_error = ErrorResponse {
+            status: "Error",
+            message: "Missing or invalid CSRF token".to_string(),
+        };
+        return Err((StatusCode::FORBIDDEN, Json(json_error)));
+    }
+    Ok(next.run(req).await)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
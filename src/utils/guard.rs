@@ -2,22 +2,29 @@ use std::sync::Arc;
 
 use axum::{
     extract::State,
-    http::{header, Request, StatusCode},
-    middleware::Next,
-    response::IntoResponse,
-    Json, body::Body,
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::{self, FromFnLayer, Next},
+    response::{IntoResponse, Response},
+    Extension, Json, body::Body,
 };
+use std::{future::Future, pin::Pin};
 use std::str::FromStr;
 use axum_extra::extract::cookie::CookieJar;
-use jsonwebtoken::{ DecodingKey, Validation};
+use chrono::Utc;
 use serde::Serialize;
 
 use crate::{
-    model::LoginModel,
+    model::{AuthMethod, LoginModel, Role},
     AppState,
 };
 
-use super::jwt::token_decode;
+use super::{
+    api_token::{authenticate_api_token, API_TOKEN_PREFIX},
+    cookies::{access_cookie, ACCESS_COOKIE},
+    jwt::{token_decode, token_encode},
+    revocation::is_token_revoked,
+    session::{find_session, touch_session},
+};
 
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
@@ -25,18 +32,13 @@ pub struct ErrorResponse {
     pub message: String,
 }
 
-// Decodes access token and returns user info
-pub async fn auth_guard(
-    cookie_jar: CookieJar,
-    State(data): State<Arc<AppState>>,
-    mut req: Request<Body>,
-    next: Next,
-) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    let token = cookie_jar
-        .get("token")
+// Reads the access token from the `token` cookie or the Authorization header
+pub fn extract_token(cookie_jar: &CookieJar, headers: &HeaderMap) -> Option<String> {
+    cookie_jar
+        .get(ACCESS_COOKIE)
         .map(|cookie| cookie.value().to_string())
         .or_else(|| {
-            req.headers()
+            headers
                 .get(header::AUTHORIZATION)
                 .and_then(|auth_header| auth_header.to_str().ok())
                 .and_then(|auth_value| {
@@ -46,7 +48,17 @@ pub async fn auth_guard(
                         None
                     }
                 })
-        });
+        })
+}
+
+// Decodes access token and returns user info
+pub async fn auth_guard(
+    cookie_jar: CookieJar,
+    State(data): State<Arc<AppState>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let token = extract_token(&cookie_jar, req.headers());
 
     let token = token.ok_or_else(|| {
         let json_error = ErrorResponse {
@@ -56,10 +68,36 @@ pub async fn auth_guard(
         (StatusCode::UNAUTHORIZED, Json(json_error))
     })?;
 
-    let claims = token_decode(
-        token, 
-        &DecodingKey::from_secret(data.env.jwt_secret.as_ref()
-    )).unwrap();
+    if token.starts_with(API_TOKEN_PREFIX) {
+        let (user, auth_method) = api_token_user(&data, &token).await?;
+        ensure_active(&user)?;
+        req.extensions_mut().insert(user);
+        req.extensions_mut().insert(auth_method);
+        return Ok(next.run(req).await);
+    }
+
+    let claims = token_decode(token, &data.keys).map_err(|(status, Json(e))| {
+        let json_error = ErrorResponse {
+            status: e.status,
+            message: e.message,
+        };
+        (status, Json(json_error))
+    })?;
+
+    let revoked = is_token_revoked(&data.db, &claims.jti).await.map_err(|e| {
+        let json_error = ErrorResponse {
+            status: "Error",
+            message: format!("Error checking token revocation: {}", e),
+        };
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error))
+    })?;
+    if revoked {
+        let json_error = ErrorResponse {
+            status: "Error",
+            message: "Token has been revoked, please log in again".to_string(),
+        };
+        return Err((StatusCode::UNAUTHORIZED, Json(json_error)));
+    }
 
     let user_id = (&claims.sub).parse::<i64>().map_err(|_| {
         let json_error = ErrorResponse {
@@ -88,6 +126,213 @@ pub async fn auth_guard(
         (StatusCode::UNAUTHORIZED, Json(json_error))
     })?;
 
+    ensure_active(&user)?;
+
+    // the user logged out everywhere after this token was issued
+    if claims.ver != user.token_version {
+        let json_error = ErrorResponse {
+            status: "Error",
+            message: "Token has been revoked, please log in again".to_string(),
+        };
+        return Err((StatusCode::UNAUTHORIZED, Json(json_error)));
+    }
+
+    // the session was revoked from another device or by an admin
+    if let Some(sid) = &claims.sid {
+        let session = find_session(&data.db, sid).await.map_err(|e| {
+            let json_error = ErrorResponse {
+                status: "Error",
+                message: format!("Error fetching session from database: {}", e),
+            };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error))
+        })?;
+        let session = session.filter(|session| session.revoked_at.is_none()).ok_or_else(|| {
+            let json_error = ErrorResponse {
+                status: "Error",
+                message: "Session has been revoked, please log in again".to_string(),
+            };
+            (StatusCode::UNAUTHORIZED, Json(json_error))
+        })?;
+        // only bookkeeping, not worth failing the request over
+        if let Err(e) = touch_session(&data.db, &session).await {
+            println!("🔥 Failed to update session last seen time: {}", e);
+        }
+    }
+
+    // sliding sessions: a cookie close to expiry is replaced with a fresh one.
+    // Bearer tokens are left alone, their clients use /api/refresh.
+    let remaining = claims.exp as i64 - Utc::now().timestamp();
+    let renewed_cookie = match &claims.sid {
+        Some(sid)
+            if data.env.sliding_sessions
+                && cookie_jar.get(ACCESS_COOKIE).is_some()
+                && remaining < data.env.sliding_session_threshold * 60 =>
+        {
+            let token = token_encode(
+                &data.keys,
+                user.id.to_string(),
+                user.token_version,
+                sid.clone(),
+                data.env.jwt_expires_in,
+            );
+            Some(access_cookie(&data.env, token))
+        }
+        _ => None,
+    };
+
     req.extensions_mut().insert(user);
+    req.extensions_mut().insert(claims);
+    req.extensions_mut().insert(AuthMethod::Session);
+    let mut response = next.run(req).await;
+    if let Some(cookie) = renewed_cookie {
+        response
+            .headers_mut()
+            .append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+    }
+    Ok(response)
+}
+
+// Deactivated accounts are refused even with a token that is otherwise valid
+fn ensure_active(user: &LoginModel) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if user.deactivated_at.is_some() {
+        let json_error = ErrorResponse {
+            status: "Error",
+            message: "This account has been deactivated".to_string(),
+        };
+        return Err((StatusCode::FORBIDDEN, Json(json_error)));
+    }
+    Ok(())
+}
+
+async fn api_token_user(
+    data: &AppState,
+    token: &str,
+) -> Result<(LoginModel, AuthMethod), (StatusCode, Json<ErrorResponse>)> {
+    let api_token = authenticate_api_token(&data.db, token)
+        .await
+        .map_err(|e| {
+            let json_error = ErrorResponse {
+                status: "Error",
+                message: format!("Error fetching API token from database: {}", e),
+            };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error))
+        })?
+        .ok_or_else(|| {
+            let json_error = ErrorResponse {
+                status: "Error",
+                message: "Invalid, expired or revoked API token".to_string(),
+            };
+            (StatusCode::UNAUTHORIZED, Json(json_error))
+        })?;
+
+    let user = sqlx::query_as::<_, LoginModel>("SELECT * FROM login WHERE id = ?")
+        .bind(api_token.user_id)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(|e| {
+            let json_error = ErrorResponse {
+                status: "Error",
+                message: format!("Error fetching user from database: {}", e),
+            };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error))
+        })?
+        .ok_or_else(|| {
+            let json_error = ErrorResponse {
+                status: "Error",
+                message: "The user belonging to this token no longer exists".to_string(),
+            };
+            (StatusCode::UNAUTHORIZED, Json(json_error))
+        })?;
+
+    let scopes = api_token.scopes.split_whitespace().map(str::to_string).collect();
+    Ok((user, AuthMethod::ApiToken { id: api_token.id, scopes }))
+}
+
+type GuardFuture = Pin<Box<dyn Future<Output = Response> + Send>>;
+type RoleGuardFn = fn(State<&'static [Role]>, Extension<LoginModel>, Request<Body>, Next) -> GuardFuture;
+type RoleGuardLayer = FromFnLayer<RoleGuardFn, &'static [Role], (State<&'static [Role]>, Extension<LoginModel>, Request<Body>)>;
+type ScopeGuardFn = fn(State<&'static str>, Extension<AuthMethod>, Request<Body>, Next) -> GuardFuture;
+type ScopeGuardLayer = FromFnLayer<ScopeGuardFn, &'static str, (State<&'static str>, Extension<AuthMethod>, Request<Body>)>;
+
+// Route layer that only lets callers with one of `roles` through. It reads the
+// LoginModel inserted by auth_guard, so auth_guard has to run first.
+pub fn require_role(roles: &'static [Role]) -> RoleGuardLayer {
+    middleware::from_fn_with_state(roles, |state, user, req, next| {
+        Box::pin(role_guard(state, user, req, next)) as GuardFuture
+    })
+}
+
+// Route layer for API tokens: they need `scope` to call the route. Logged in
+// users (JWT) are only limited by require_role.
+pub fn require_scope(scope: &'static str) -> ScopeGuardLayer {
+    middleware::from_fn_with_state(scope, |state, auth_method, req, next| {
+        Box::pin(scope_guard(state, auth_method, req, next)) as GuardFuture
+    })
+}
+
+async fn role_guard(
+    State(roles): State<&'static [Role]>,
+    Extension(user): Extension<LoginModel>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    match user.role() {
+        Some(role) if roles.contains(&role) => next.run(req).await,
+        _ => {
+            let json_error = ErrorResponse {
+                status: "Error",
+                message: "You do not have permission to perform this action".to_string(),
+            };
+            (StatusCode::FORBIDDEN, Json(json_error)).into_response()
+        }
+    }
+}
+
+async fn scope_guard(
+    State(scope): State<&'static str>,
+    Extension(auth_method): Extension<AuthMethod>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if auth_method.allows(scope) {
+        return next.run(req).await;
+    }
+    let json_error = ErrorResponse {
+        status: "Error",
+        message: format!("This API token is missing the `{}` scope", scope),
+    };
+    (StatusCode::FORBIDDEN, Json(json_error)).into_response()
+}
+
+// For account management routes: API tokens can't call them, whatever their scopes
+pub async fn require_session(
+    Extension(auth_method): Extension<AuthMethod>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if let AuthMethod::ApiToken { .. } = auth_method {
+        let json_error = ErrorResponse {
+            status: "Error",
+            message: "This action requires logging in, API tokens can't be used".to_string(),
+        };
+        return Err((StatusCode::FORBIDDEN, Json(json_error)));
+    }
     Ok(next.run(req).await)
-}
\ No newline at end of file
+}
+
+// Keeps accounts that haven't verified their email yet (only possible with
+// UNVERIFIED_LOGIN=limited) away from the route. Runs after auth_guard.
+pub async fn require_verified(
+    Extension(user): Extension<LoginModel>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if user.email_verified_at.is_none() {
+        let json_error = ErrorResponse {
+            status: "Error",
+            message: "Please verify your email address".to_string(),
+        };
+        return Err((StatusCode::FORBIDDEN, Json(json_error)));
+    }
+    Ok(next.run(req).await)
+}
@@ -2,7 +2,8 @@ use axum::{http::StatusCode, response::IntoResponse, Json};
 use chrono::{Utc, Duration};
 use jsonwebtoken::{encode, Header, EncodingKey, errors::Error, TokenData, decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
-use crate::{model::TokenClaims, utils};
+use crate::{model::{MfaClaims, OidcFlowClaims, TokenClaims}, utils};
+use super::keys::JwtKeys;
 use dotenv::dotenv;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -12,35 +13,109 @@ pub struct ErrorResponse {
 }
 
 pub fn token_encode(
-    header: &Header,
+    keys: &JwtKeys,
     id: String,
-    key: &EncodingKey,
+    version: i32,
+    session_id: String,
+    expires_in: Duration,
 ) -> String {
     let now = chrono::Utc::now();
     let iat = now.timestamp() as usize;
-    let exp = (now + chrono::Duration::minutes(60)).timestamp() as usize;
+    let exp = (now + expires_in).timestamp() as usize;
     let claims: TokenClaims = TokenClaims {
         sub: id,
         exp,
         iat,
+        jti: uuid::Uuid::new_v4().to_string(),
+        ver: version,
+        sid: Some(session_id),
     };
-    let token = encode(header, &claims, key);
+    let token = encode(&keys.header(), &claims, keys.encoding_key());
     return token.expect("returns encoded token string");
 }
 
-pub fn token_decode(token: String, key: &DecodingKey) 
+pub fn token_decode(token: String, keys: &JwtKeys) 
 ->  Result<TokenClaims, (StatusCode, Json<ErrorResponse>)> {
+    let invalid_token = || {
+        let json_error = ErrorResponse {
+            status: "Error",
+            message: "Invalid token".to_string(),
+        };
+        (StatusCode::UNAUTHORIZED, Json(json_error))
+    };
+    let (key, validation) = keys.decoding_key(&token).ok_or_else(invalid_token)?;
     let claims = decode::<TokenClaims>(
         &token,
         key,
-        &Validation::default(),
+        &validation,
     )
-    .map_err(|_| {
+    .map_err(|_| invalid_token())?.claims;
+    return Ok(claims);
+}
+
+const MFA_AUDIENCE: &str = "mfa";
+
+pub fn mfa_token_encode(id: String, keys: &JwtKeys) -> String {
+    let now = chrono::Utc::now();
+    let claims = MfaClaims {
+        sub: id,
+        iat: now.timestamp() as usize,
+        exp: (now + chrono::Duration::minutes(5)).timestamp() as usize,
+        aud: MFA_AUDIENCE.to_string(),
+    };
+    encode(&keys.header(), &claims, keys.encoding_key()).expect("returns encoded token string")
+}
+
+pub fn mfa_token_decode(token: &str, keys: &JwtKeys)
+->  Result<MfaClaims, (StatusCode, Json<ErrorResponse>)> {
+    let invalid_token = || {
         let json_error = ErrorResponse {
             status: "Error",
-            message: "Invalid token".to_string(),
+            message: "Invalid or expired two-factor login, please log in again".to_string(),
         };
         (StatusCode::UNAUTHORIZED, Json(json_error))
-    })?.claims;
-    return Ok(claims);
-}
\ No newline at end of file
+    };
+    let (key, mut validation) = keys.decoding_key(token).ok_or_else(invalid_token)?;
+    validation.set_audience(&[MFA_AUDIENCE]);
+    let claims = decode::<MfaClaims>(token, key, &validation)
+        .map_err(|_| invalid_token())?.claims;
+    Ok(claims)
+}
+
+const OIDC_FLOW_AUDIENCE: &str = "oidc";
+
+pub fn oidc_flow_encode(
+    state: String,
+    nonce: String,
+    code_verifier: String,
+    link_user_id: Option<i64>,
+    keys: &JwtKeys,
+) -> String {
+    let now = chrono::Utc::now();
+    let claims = OidcFlowClaims {
+        state,
+        nonce,
+        code_verifier,
+        link_user_id,
+        iat: now.timestamp() as usize,
+        exp: (now + chrono::Duration::minutes(10)).timestamp() as usize,
+        aud: OIDC_FLOW_AUDIENCE.to_string(),
+    };
+    encode(&keys.header(), &claims, keys.encoding_key()).expect("returns encoded token string")
+}
+
+pub fn oidc_flow_decode(token: &str, keys: &JwtKeys)
+->  Result<OidcFlowClaims, (StatusCode, Json<ErrorResponse>)> {
+    let invalid_token = || {
+        let json_error = ErrorResponse {
+            status: "Error",
+            message: "Single sign-on attempt expired or invalid, please try again".to_string(),
+        };
+        (StatusCode::BAD_REQUEST, Json(json_error))
+    };
+    let (key, mut validation) = keys.decoding_key(token).ok_or_else(invalid_token)?;
+    validation.set_audience(&[OIDC_FLOW_AUDIENCE]);
+    let claims = decode::<OidcFlowClaims>(token, key, &validation)
+        .map_err(|_| invalid_token())?.claims;
+    Ok(claims)
+}
@@ -0,0 +1,191 @@
+use std::{collections::HashMap, fs};
+
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine};
+use jsonwebtoken::{
+    decode_header,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use rsa::{pkcs1::DecodeRsaPublicKey, pkcs8::DecodePublicKey, traits::PublicKeyParts, RsaPublicKey};
+
+use crate::config::Config;
+
+// Keys used to sign and verify our JWTs.
+//
+// With HS256 (the default) everything uses JWT_SECRET. With RS256 or EdDSA
+// tokens are signed with the private key JWT_SIGNING_KEY_FILE and carry its
+// JWT_SIGNING_KEY_ID as `kid`. Every public key in JWT_VERIFICATION_KEYS is
+// accepted and published at /.well-known/jwks.json, so a new key can be
+// published before it is used and an old one kept until its tokens expire.
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    signing_kid: Option<String>,
+    encoding_key: EncodingKey,
+    // kid -> key, for tokens signed with an asymmetric key
+    verification_keys: HashMap<String, (Algorithm, DecodingKey)>,
+    // for tokens without a kid: HS256 with JWT_SECRET, also while switching to asymmetric keys
+    secret_key: Option<DecodingKey>,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    pub fn from_config(config: &Config) -> JwtKeys {
+        let algorithm = match config.jwt_algorithm.as_str() {
+            "HS256" => Algorithm::HS256,
+            "RS256" => Algorithm::RS256,
+            "EdDSA" => Algorithm::EdDSA,
+            other => panic!("JWT_ALGORITHM must be HS256, RS256 or EdDSA, got {}", other),
+        };
+        let secret_key = if config.jwt_secret.is_empty() {
+            None
+        } else {
+            Some(DecodingKey::from_secret(config.jwt_secret.as_ref()))
+        };
+
+        if algorithm == Algorithm::HS256 {
+            if secret_key.is_none() {
+                panic!("JWT_SECRET must be set when JWT_ALGORITHM is HS256");
+            }
+            return JwtKeys {
+                algorithm,
+                signing_kid: None,
+                encoding_key: EncodingKey::from_secret(config.jwt_secret.as_ref()),
+                verification_keys: HashMap::new(),
+                secret_key,
+                // a shared secret is never published
+                jwks: JwkSet { keys: vec![] },
+            };
+        }
+
+        let signing_kid = config
+            .jwt_signing_key_id
+            .clone()
+            .expect("JWT_SIGNING_KEY_ID must be set for asymmetric JWT signing");
+        let signing_key_file = config
+            .jwt_signing_key_file
+            .clone()
+            .expect("JWT_SIGNING_KEY_FILE must be set for asymmetric JWT signing");
+        let private_pem = read_pem(&signing_key_file);
+        let encoding_key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_pem),
+            _ => EncodingKey::from_ed_pem(&private_pem),
+        }
+        .unwrap_or_else(|e| panic!("Invalid signing key {}: {}", signing_key_file, e));
+
+        let mut verification_keys = HashMap::new();
+        let mut jwks = JwkSet { keys: vec![] };
+        for (kid, path) in &config.jwt_verification_keys {
+            let public_pem = read_pem(path);
+            let (decoding_key, jwk) = match algorithm {
+                Algorithm::RS256 => rsa_public_key(kid, &public_pem),
+                _ => ed_public_key(kid, &public_pem),
+            }
+            .unwrap_or_else(|e| panic!("Invalid verification key {}: {}", path, e));
+            verification_keys.insert(kid.clone(), (algorithm, decoding_key));
+            jwks.keys.push(jwk);
+        }
+        if !verification_keys.contains_key(&signing_kid) {
+            panic!("JWT_VERIFICATION_KEYS must contain the public key of {}", signing_kid);
+        }
+
+        JwtKeys {
+            algorithm,
+            signing_kid: Some(signing_kid),
+            encoding_key,
+            verification_keys,
+            secret_key,
+            jwks,
+        }
+    }
+
+    pub fn header(&self) -> Header {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.signing_kid.clone();
+        header
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    // Picks the key named by the token's kid. The algorithm comes from our
+    // side, never from the token header.
+    pub fn decoding_key(&self, token: &str) -> Option<(&DecodingKey, Validation)> {
+        let header = decode_header(token).ok()?;
+        match header.kid {
+            Some(kid) => self
+                .verification_keys
+                .get(&kid)
+                .map(|(algorithm, key)| (key, Validation::new(*algorithm))),
+            None => self
+                .secret_key
+                .as_ref()
+                .map(|key| (key, Validation::new(Algorithm::HS256))),
+        }
+    }
+
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+fn read_pem(path: &str) -> Vec<u8> {
+    fs::read(path).unwrap_or_else(|e| panic!("Failed to read key file {}: {}", path, e))
+}
+
+fn common_parameters(kid: &str, algorithm: KeyAlgorithm) -> CommonParameters {
+    CommonParameters {
+        public_key_use: Some(PublicKeyUse::Signature),
+        key_algorithm: Some(algorithm),
+        key_id: Some(kid.to_string()),
+        ..Default::default()
+    }
+}
+
+fn rsa_public_key(kid: &str, pem: &[u8]) -> Result<(DecodingKey, Jwk), String> {
+    let pem = std::str::from_utf8(pem).map_err(|e| e.to_string())?;
+    // accepts both "BEGIN PUBLIC KEY" and "BEGIN RSA PUBLIC KEY"
+    let key = RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .map_err(|e| e.to_string())?;
+    let n = key.n().to_bytes_be();
+    let e = key.e().to_bytes_be();
+
+    let jwk = Jwk {
+        common: common_parameters(kid, KeyAlgorithm::RS256),
+        algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: URL_SAFE_NO_PAD.encode(&n),
+            e: URL_SAFE_NO_PAD.encode(&e),
+        }),
+    };
+    Ok((DecodingKey::from_rsa_raw_components(&n, &e), jwk))
+}
+
+fn ed_public_key(kid: &str, pem: &[u8]) -> Result<(DecodingKey, Jwk), String> {
+    let pem = std::str::from_utf8(pem).map_err(|e| e.to_string())?;
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    let der = STANDARD.decode(body.trim()).map_err(|e| e.to_string())?;
+    // an Ed25519 SubjectPublicKeyInfo is a fixed 12 byte prefix followed by the 32 byte key
+    if der.len() != 44 {
+        return Err("expected an Ed25519 public key in SubjectPublicKeyInfo PEM format".to_string());
+    }
+    let x = URL_SAFE_NO_PAD.encode(&der[12..]);
+
+    let jwk = Jwk {
+        common: common_parameters(kid, KeyAlgorithm::EdDSA),
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: x.clone(),
+        }),
+    };
+    let decoding_key = DecodingKey::from_ed_components(&x).map_err(|e| e.to_string())?;
+    Ok((decoding_key, jwk))
+}
@@ -0,0 +1,80 @@
+use std::{fmt, fs::OpenOptions, io::Write, path::PathBuf, sync::Arc};
+
+use crate::config::Config;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct MailError(pub String);
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to send email: {}", self.0)
+    }
+}
+
+// Anything that can deliver an email. Swap in an SMTP or API backed
+// implementation for production; the ones below are for dev and tests.
+// Sending may block, use send_email from async code.
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: &Email) -> Result<(), MailError>;
+}
+
+// Prints every email to stdout
+pub struct StdoutMailer;
+
+impl Mailer for StdoutMailer {
+    fn send(&self, email: &Email) -> Result<(), MailError> {
+        println!(
+            "📧 To: {}\nSubject: {}\n\n{}\n",
+            email.to, email.subject, email.body
+        );
+        Ok(())
+    }
+}
+
+// Appends every email to a file
+pub struct FileMailer {
+    path: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileMailer { path: path.into() }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: &Email) -> Result<(), MailError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| MailError(e.to_string()))?;
+        writeln!(
+            file,
+            "To: {}\nSubject: {}\n\n{}\n---",
+            email.to, email.subject, email.body
+        )
+        .map_err(|e| MailError(e.to_string()))
+    }
+}
+
+pub fn mailer_from_config(config: &Config) -> Arc<dyn Mailer> {
+    match config.mailer.as_str() {
+        "file" => Arc::new(FileMailer::new(&config.mailer_file)),
+        _ => Arc::new(StdoutMailer),
+    }
+}
+
+// Runs the mailer on the blocking thread pool so it doesn't stall the runtime
+pub async fn send_email(mailer: Arc<dyn Mailer>, email: Email) -> Result<(), MailError> {
+    tokio::task::spawn_blocking(move || mailer.send(&email))
+        .await
+        .map_err(|e| MailError(e.to_string()))?
+}
@@ -1,2 +1,17 @@
 pub mod jwt;
 pub mod guard;
+pub mod refresh;
+pub mod token;
+pub mod revocation;
+pub mod mailer;
+pub mod throttle;
+pub mod totp;
+pub mod api_token;
+pub mod keys;
+pub mod cookies;
+pub mod password;
+pub mod csrf;
+pub mod audit;
+pub mod oidc;
+pub mod session;
+pub mod password_policy;
@@ -0,0 +1,320 @@
+use std::{collections::HashMap, fmt};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, jwk::Jwk, Algorithm, DecodingKey, Validation};
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+use url::Url;
+
+use crate::{config::Config, model::Role};
+
+#[derive(Debug)]
+pub struct OidcError(pub String);
+
+impl fmt::Display for OidcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "single sign-on failed: {}", self.0)
+    }
+}
+
+// The parts of the provider's /.well-known/openid-configuration we use
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+// Who the provider says logged in, taken from a validated ID token
+#[derive(Debug, Clone)]
+pub struct OidcIdentity {
+    pub subject: String,
+    pub email: String,
+    // only true when the token says so, a missing claim counts as unverified
+    pub email_verified: bool,
+    pub name: Option<String>,
+    // None when the token has no groups claim at all
+    pub groups: Option<Vec<String>>,
+}
+
+// Authorization code flow with PKCE against a single OpenID Connect provider.
+// The discovery document and the provider's keys are fetched on first use; the
+// keys are fetched again when an ID token names a key we don't know yet.
+pub struct OidcClient {
+    http: reqwest::Client,
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: String,
+    scopes: String,
+    groups_claim: String,
+    role_mapping: Vec<(String, Role)>,
+    default_role: Role,
+    metadata: RwLock<Option<ProviderMetadata>>,
+    keys: RwLock<HashMap<String, Jwk>>,
+}
+
+impl OidcClient {
+    // None unless OIDC_ISSUER is set
+    pub fn from_config(config: &Config) -> Option<OidcClient> {
+        let issuer = config.oidc_issuer.clone()?;
+        let parse_role = |role: &str| {
+            role.parse::<Role>()
+                .unwrap_or_else(|e| panic!("Invalid role in OIDC configuration: {}", e))
+        };
+        Some(OidcClient {
+            http: reqwest::Client::new(),
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: config
+                .oidc_client_id
+                .clone()
+                .expect("OIDC_CLIENT_ID must be set when OIDC_ISSUER is set"),
+            client_secret: config.oidc_client_secret.clone(),
+            redirect_url: config.oidc_redirect_url.clone(),
+            scopes: config.oidc_scopes.clone(),
+            groups_claim: config.oidc_groups_claim.clone(),
+            role_mapping: config
+                .oidc_role_mapping
+                .iter()
+                .map(|(group, role)| (group.clone(), parse_role(role)))
+                .collect(),
+            default_role: parse_role(&config.oidc_default_role),
+            metadata: RwLock::new(None),
+            keys: RwLock::new(HashMap::new()),
+        })
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata, OidcError> {
+        if let Some(metadata) = self.metadata.read().await.as_ref() {
+            return Ok(metadata.clone());
+        }
+        let url = format!("{}/.well-known/openid-configuration", self.issuer);
+        let metadata: ProviderMetadata = self.get_json(&url).await?;
+        if metadata.issuer.trim_end_matches('/') != self.issuer {
+            return Err(OidcError(format!("provider reports issuer {}", metadata.issuer)));
+        }
+        *self.metadata.write().await = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, OidcError> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| OidcError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError(e.to_string()))
+    }
+
+    // Where to send the browser to log in
+    pub async fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> Result<String, OidcError> {
+        let metadata = self.metadata().await?;
+        let mut url = Url::parse(&metadata.authorization_endpoint).map_err(|e| OidcError(e.to_string()))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_url)
+            .append_pair("scope", &self.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.to_string())
+    }
+
+    // Redeems the authorization code and validates the ID token that comes back
+    pub async fn exchange_code(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<OidcIdentity, OidcError> {
+        let metadata = self.metadata().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_url.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret.as_str()));
+        }
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| OidcError(e.to_string()))?;
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(OidcError(format!("token endpoint answered: {}", body)));
+        }
+        let tokens: TokenResponse = response.json().await.map_err(|e| OidcError(e.to_string()))?;
+
+        self.validate_id_token(&metadata, &tokens.id_token, nonce).await
+    }
+
+    async fn validate_id_token(&self, metadata: &ProviderMetadata, id_token: &str, nonce: &str) -> Result<OidcIdentity, OidcError> {
+        let header = decode_header(id_token).map_err(|e| OidcError(e.to_string()))?;
+        // only asymmetric signatures, the keys have to come from the provider's JWKS
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(OidcError("ID token is not signed with an asymmetric key".to_string()));
+        }
+        let jwk = self.signing_key(metadata, header.kid.as_deref()).await?;
+        let key = DecodingKey::from_jwk(&jwk).map_err(|e| OidcError(e.to_string()))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+        let claims = decode::<HashMap<String, Value>>(id_token, &key, &validation)
+            .map_err(|e| OidcError(format!("invalid ID token: {}", e)))?
+            .claims;
+
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            return Err(OidcError("ID token nonce doesn't match".to_string()));
+        }
+        let subject = claims.get("sub").and_then(Value::as_str).unwrap_or_default();
+        let email = claims.get("email").and_then(Value::as_str).unwrap_or_default();
+        if subject.is_empty() || email.is_empty() {
+            return Err(OidcError("ID token has no subject or email, request the email scope".to_string()));
+        }
+        let groups = claims.get(&self.groups_claim).map(|groups| match groups {
+            Value::Array(groups) => groups.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+            Value::String(group) => vec![group.clone()],
+            _ => vec![],
+        });
+
+        Ok(OidcIdentity {
+            subject: subject.to_string(),
+            email: email.to_ascii_lowercase(),
+            email_verified: claims.get("email_verified").and_then(Value::as_bool) == Some(true),
+            name: claims.get("name").and_then(Value::as_str).map(str::to_string),
+            groups,
+        })
+    }
+
+    async fn signing_key(&self, metadata: &ProviderMetadata, kid: Option<&str>) -> Result<Jwk, OidcError> {
+        let kid = kid.unwrap_or_default().to_string();
+        if let Some(jwk) = self.keys.read().await.get(&kid) {
+            return Ok(jwk.clone());
+        }
+
+        // unknown key: the provider may have rotated, fetch the set again
+        let jwks: Value = self.get_json(&metadata.jwks_uri).await?;
+        let mut keys = HashMap::new();
+        for key in jwks["keys"].as_array().cloned().unwrap_or_default() {
+            // keys we can't use (e.g. encryption keys) are skipped
+            if let Ok(jwk) = serde_json::from_value::<Jwk>(key) {
+                keys.insert(jwk.common.key_id.clone().unwrap_or_default(), jwk);
+            }
+        }
+        let jwk = keys.get(&kid).cloned();
+        *self.keys.write().await = keys;
+        jwk.ok_or_else(|| OidcError(format!("unknown signing key `{}`", kid)))
+    }
+
+    // The most privileged role any of the groups maps to. Without a groups claim
+    // there is nothing to go on: None, the caller keeps the current role.
+    pub fn role_for(&self, groups: Option<&[String]>) -> Option<Role> {
+        let groups = groups?;
+        let mapped = self
+            .role_mapping
+            .iter()
+            .filter(|(group, _)| groups.contains(group))
+            .map(|(_, role)| *role);
+        [Role::Admin, Role::Agent, Role::User]
+            .into_iter()
+            .find(|role| mapped.clone().any(|mapped| mapped == *role))
+            .or(Some(self.default_role))
+    }
+
+    pub fn default_role(&self) -> Role {
+        self.default_role
+    }
+}
+
+// PKCE verifier and its S256 challenge
+pub fn generate_pkce() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let verifier = URL_SAFE_NO_PAD.encode(bytes);
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    (verifier, challenge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> OidcClient {
+        OidcClient {
+            http: reqwest::Client::new(),
+            issuer: "http://localhost:8080/default".to_string(),
+            client_id: "ticketing".to_string(),
+            client_secret: None,
+            redirect_url: "http://localhost:3000/api/oidc/callback".to_string(),
+            scopes: "openid email".to_string(),
+            groups_claim: "groups".to_string(),
+            role_mapping: vec![
+                ("helpdesk-admins".to_string(), Role::Admin),
+                ("helpdesk".to_string(), Role::Agent),
+            ],
+            default_role: Role::User,
+            metadata: RwLock::new(None),
+            keys: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn groups(groups: &[&str]) -> Vec<String> {
+        groups.iter().map(|group| group.to_string()).collect()
+    }
+
+    #[test]
+    fn role_for_keeps_the_role_without_a_groups_claim() {
+        assert_eq!(client().role_for(None), None);
+    }
+
+    #[test]
+    fn role_for_falls_back_to_the_default_role() {
+        let client = client();
+        assert_eq!(client.role_for(Some(&[])), Some(Role::User));
+        assert_eq!(client.role_for(Some(&groups(&["marketing"]))), Some(Role::User));
+    }
+
+    #[test]
+    fn role_for_picks_the_most_privileged_mapped_role() {
+        let client = client();
+        assert_eq!(client.role_for(Some(&groups(&["helpdesk"]))), Some(Role::Agent));
+        assert_eq!(
+            client.role_for(Some(&groups(&["helpdesk", "marketing", "helpdesk-admins"]))),
+            Some(Role::Admin)
+        );
+    }
+
+    #[test]
+    fn role_for_matches_group_names_exactly() {
+        let client = client();
+        assert_eq!(client.role_for(Some(&groups(&["Helpdesk-Admins", "helpdesk-admins-old"]))), Some(Role::User));
+    }
+
+    #[test]
+    fn pkce_challenge_is_the_s256_of_the_verifier() {
+        let (verifier, challenge) = generate_pkce();
+        assert_eq!(verifier.len(), 43);
+        assert_eq!(challenge, URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())));
+    }
+
+    #[test]
+    fn pkce_verifiers_are_random() {
+        assert_ne!(generate_pkce().0, generate_pkce().0);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{fmt, sync::Arc};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+
+use crate::config::Config;
+
+#[derive(Debug)]
+pub struct PasswordError(pub String);
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to hash password: {}", self.0)
+    }
+}
+
+// Hashes new passwords with one algorithm and verifies hashes of every
+// algorithm we ever used, so accounts created before a switch keep working.
+// These calls are slow on purpose, use hash_password / verify_password from
+// async code.
+pub trait PasswordHasher: Send + Sync {
+    fn hash(&self, password: &str) -> Result<String, PasswordError>;
+    fn verify(&self, password: &str, hash: &str) -> bool {
+        verify_any(password, hash)
+    }
+    // true when `hash` uses another algorithm or weaker parameters than new hashes
+    fn needs_rehash(&self, hash: &str) -> bool;
+}
+
+// Argon2id, the default
+pub struct Argon2Hasher {
+    params: Params,
+}
+
+impl Argon2Hasher {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Self {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .unwrap_or_else(|e| panic!("Invalid Argon2 parameters: {}", e));
+        Argon2Hasher { params }
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+impl PasswordHasher for Argon2Hasher {
+    fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| PasswordError(e.to_string()))
+    }
+
+    fn needs_rehash(&self, hash: &str) -> bool {
+        let parsed = match PasswordHash::new(hash) {
+            Ok(parsed) => parsed,
+            Err(_) => return true,
+        };
+        if parsed.algorithm != Algorithm::Argon2id.ident() || parsed.version != Some(Version::V0x13.into()) {
+            return true;
+        }
+        match Params::try_from(&parsed) {
+            Ok(params) => {
+                params.m_cost() < self.params.m_cost()
+                    || params.t_cost() < self.params.t_cost()
+                    || params.p_cost() < self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+// bcrypt, what the app used before Argon2id
+pub struct BcryptHasher {
+    cost: u32,
+}
+
+impl BcryptHasher {
+    pub fn new(cost: u32) -> Self {
+        BcryptHasher { cost }
+    }
+}
+
+impl PasswordHasher for BcryptHasher {
+    fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        bcrypt::hash(password, self.cost).map_err(|e| PasswordError(e.to_string()))
+    }
+
+    fn needs_rehash(&self, hash: &str) -> bool {
+        // $2b$<cost>$<salt and hash>
+        match bcrypt_cost(hash) {
+            Some(cost) => cost < self.cost,
+            None => true,
+        }
+    }
+}
+
+fn bcrypt_cost(hash: &str) -> Option<u32> {
+    if !hash.starts_with("$2") {
+        return None;
+    }
+    hash.split('$').nth(2)?.parse().ok()
+}
+
+// Picks the algorithm from the hash itself
+fn verify_any(password: &str, hash: &str) -> bool {
+    if hash.starts_with("$argon2") {
+        match PasswordHash::new(hash) {
+            Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+            Err(_) => false,
+        }
+    } else if bcrypt_cost(hash).is_some() {
+        bcrypt::verify(password, hash).unwrap_or(false)
+    } else {
+        false
+    }
+}
+
+pub fn hasher_from_config(config: &Config) -> Arc<dyn PasswordHasher> {
+    match config.password_hasher.as_str() {
+        "bcrypt" => Arc::new(BcryptHasher::new(config.bcrypt_cost)),
+        _ => Arc::new(Argon2Hasher::new(
+            config.argon2_memory_kib,
+            config.argon2_iterations,
+            config.argon2_parallelism,
+        )),
+    }
+}
+
+// Runs the hasher on the blocking thread pool so it doesn't stall the runtime
+pub async fn hash_password(hasher: Arc<dyn PasswordHasher>, password: String) -> Result<String, PasswordError> {
+    tokio::task::spawn_blocking(move || hasher.hash(&password))
+        .await
+        .map_err(|e| PasswordError(e.to_string()))?
+}
+
+pub async fn verify_password(hasher: Arc<dyn PasswordHasher>, password: String, hash: String) -> bool {
+    tokio::task::spawn_blocking(move || hasher.verify(&password, &hash))
+        .await
+        .unwrap_or(false)
+}
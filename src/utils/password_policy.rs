@@ -0,0 +1,191 @@
+use std::{collections::HashSet, fs};
+
+use serde_json::{json, Value};
+
+use crate::config::Config;
+
+// What a new password has to satisfy. Only checked when a password is set
+// (registration, reset, change), existing passwords keep working.
+pub struct PasswordPolicy {
+    min_length: usize,
+    min_entropy: f64,
+    // lowercased
+    breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn from_config(config: &Config) -> PasswordPolicy {
+        let breached = match &config.breached_passwords_file {
+            Some(path) => {
+                let breached: HashSet<String> = fs::read_to_string(path)
+                    .unwrap_or_else(|e| panic!("Failed to read breached password list {}: {}", path, e))
+                    .lines()
+                    .map(|line| line.trim().to_lowercase())
+                    .filter(|line| !line.is_empty())
+                    .collect();
+                println!("✅ Loaded {} breached passwords", breached.len());
+                breached
+            }
+            None => HashSet::new(),
+        };
+        PasswordPolicy {
+            min_length: config.password_min_length,
+            min_entropy: config.password_min_entropy,
+            breached,
+        }
+    }
+
+    // Every rule the password breaks, empty when it is acceptable
+    pub fn check(&self, password: &str, email: &str, name: &str) -> Vec<String> {
+        let mut errors = vec![];
+        let lowercase = password.to_lowercase();
+
+        if password.chars().count() < self.min_length {
+            errors.push(format!("Must be at least {} characters long", self.min_length));
+        }
+        if estimate_entropy(password) < self.min_entropy {
+            errors.push("Too easy to guess, use a longer password or more kinds of characters".to_string());
+        }
+        if personal_parts(email, name).iter().any(|part| lowercase.contains(part.as_str())) {
+            errors.push("Must not contain your name or email address".to_string());
+        }
+        if self.breached.contains(&lowercase) {
+            errors.push("Appears in a list of leaked passwords, choose another one".to_string());
+        }
+        errors
+    }
+
+    // check() as field-level errors: `{ "<field>": ["..."] }`, None when acceptable
+    pub fn field_errors(&self, field: &str, password: &str, email: &str, name: &str) -> Option<Value> {
+        let errors = self.check(password, email, name);
+        if errors.is_empty() {
+            return None;
+        }
+        Some(json!({ field: errors }))
+    }
+}
+
+// Bits of a random string drawn from the character classes the password uses.
+// Repeated and sequential characters ("aaaa", "1234", "abcd") only count one bit.
+fn estimate_entropy(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    let mut pool = 0;
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') {
+        pool += 33;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    let bits_per_char = (pool as f64).log2();
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let predictable = i > 0 && (*c as i64 - chars[i - 1] as i64).abs() <= 1;
+            if predictable { 1.0 } else { bits_per_char }
+        })
+        .sum()
+}
+
+// The email, its local part and every word of the name, lowercased. Parts
+// shorter than 3 characters would match too many passwords by accident.
+fn personal_parts(email: &str, name: &str) -> Vec<String> {
+    let email = email.trim().to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default().to_string();
+    let mut parts = vec![email.clone(), local_part];
+    parts.extend(name.to_lowercase().split_whitespace().map(str::to_string));
+    parts.retain(|part| part.chars().count() >= 3);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 10,
+            min_entropy: 40.0,
+            breached: ["correcthorse99!"].iter().map(|password| password.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn entropy_counts_the_character_classes_used() {
+        let lowercase = estimate_entropy("qzmxnwkv");
+        let mixed = estimate_entropy("qZ7#nW2!");
+        assert!(mixed > lowercase);
+        assert!((lowercase - 8.0 * 26f64.log2()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn entropy_counts_repeated_and_sequential_characters_as_one_bit() {
+        let bits_per_char = 26f64.log2();
+        assert!((estimate_entropy("aaaaaaaa") - (bits_per_char + 7.0)).abs() < 1e-9);
+        assert!((estimate_entropy("abcdefgh") - (bits_per_char + 7.0)).abs() < 1e-9);
+        assert!(estimate_entropy("1234567890") < 40.0);
+    }
+
+    #[test]
+    fn entropy_of_an_empty_password_is_zero() {
+        assert_eq!(estimate_entropy(""), 0.0);
+    }
+
+    #[test]
+    fn personal_parts_include_the_email_its_local_part_and_name_words() {
+        let parts = personal_parts(" Jane.Doe@Example.com ", "Jane van Doe");
+        assert_eq!(parts, vec!["jane.doe@example.com", "jane.doe", "jane", "van", "doe"]);
+    }
+
+    #[test]
+    fn personal_parts_skip_short_words() {
+        let parts = personal_parts("jo@example.com", "Jo Li");
+        assert_eq!(parts, vec!["jo@example.com"]);
+    }
+
+    #[test]
+    fn check_accepts_a_strong_password() {
+        assert!(policy().check("vX8#qLm2!tRw", "jane@example.com", "Jane Doe").is_empty());
+    }
+
+    #[test]
+    fn check_names_every_broken_rule() {
+        let errors = policy().check("jane", "jane@example.com", "Jane Doe");
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("at least 10 characters"));
+        assert!(errors[2].contains("name or email"));
+    }
+
+    #[test]
+    fn check_rejects_personal_info_in_any_case() {
+        let errors = policy().check("xQ7#DOE!pL2v", "jane@example.com", "Jane Doe");
+        assert_eq!(errors, vec!["Must not contain your name or email address"]);
+    }
+
+    #[test]
+    fn check_compares_breached_passwords_case_insensitively() {
+        let errors = policy().check("CorrectHorse99!", "jane@example.com", "Jane Doe");
+        assert!(errors.iter().any(|error| error.contains("leaked")));
+    }
+
+    #[test]
+    fn field_errors_are_keyed_by_field() {
+        let policy = policy();
+        assert_eq!(policy.field_errors("password", "vX8#qLm2!tRw", "jane@example.com", "Jane"), None);
+        let fields = policy.field_errors("new_password", "short", "jane@example.com", "Jane").unwrap();
+        assert!(fields["new_password"].as_array().is_some_and(|errors| !errors.is_empty()));
+    }
+}
@@ -0,0 +1,66 @@
+use chrono::{Duration, Utc};
+use sqlx::MySqlPool;
+
+use crate::model::RefreshTokenModel;
+
+use super::token::{generate_token, hash_token};
+
+// Stores a new refresh token for the user and returns the raw token.
+// Tokens issued by rotation share the family_id of the login they came from,
+// so a reused token can take down the whole chain.
+pub async fn issue_refresh_token(
+    db: &MySqlPool,
+    user_id: i64,
+    family_id: &str,
+    max_age_days: i64,
+) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::days(max_age_days);
+
+    sqlx::query(
+        r#"INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at) VALUES (?, ?, ?, ?)"#,
+    )
+    .bind(user_id)
+    .bind(family_id)
+    .bind(hash_token(&token))
+    .bind(expires_at)
+    .execute(db)
+    .await?;
+
+    Ok(token)
+}
+
+pub async fn find_refresh_token(
+    db: &MySqlPool,
+    token: &str,
+) -> Result<Option<RefreshTokenModel>, sqlx::Error> {
+    sqlx::query_as::<_, RefreshTokenModel>(r#"SELECT * FROM refresh_tokens WHERE token_hash = ?"#)
+        .bind(hash_token(token))
+        .fetch_optional(db)
+        .await
+}
+
+// Marks a single token as used. Returns false when someone else got there first.
+pub async fn revoke_refresh_token(db: &MySqlPool, id: i64) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"UPDATE refresh_tokens SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL"#,
+    )
+    .bind(Utc::now())
+    .bind(id)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn revoke_refresh_family(db: &MySqlPool, family_id: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"UPDATE refresh_tokens SET revoked_at = ? WHERE family_id = ? AND revoked_at IS NULL"#,
+    )
+    .bind(Utc::now())
+    .bind(family_id)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use sqlx::MySqlPool;
+
+use crate::model::TokenClaims;
+
+// Puts the token's jti on the deny list until the token would have expired anyway
+pub async fn revoke_access_token(db: &MySqlPool, claims: &TokenClaims) -> Result<(), sqlx::Error> {
+    let user_id = claims.sub.parse::<i64>().unwrap_or_default();
+    let expires_at = DateTime::<Utc>::from_timestamp(claims.exp as i64, 0).unwrap_or_else(Utc::now);
+
+    sqlx::query(r#"INSERT IGNORE INTO revoked_tokens (jti, user_id, expires_at) VALUES (?, ?, ?)"#)
+        .bind(&claims.jti)
+        .bind(user_id)
+        .bind(expires_at)
+        .execute(db)
+        .await?;
+
+    // expired entries can't match a valid token any more
+    sqlx::query(r#"DELETE FROM revoked_tokens WHERE expires_at < ?"#)
+        .bind(Utc::now())
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn is_token_revoked(db: &MySqlPool, jti: &str) -> Result<bool, sqlx::Error> {
+    let revoked = sqlx::query(r#"SELECT jti FROM revoked_tokens WHERE jti = ?"#)
+        .bind(jti)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(revoked.is_some())
+}
+
+// "Log out everywhere": every access token minted before this call carries an
+// older token_version and all refresh tokens and sessions of the user are revoked.
+pub async fn revoke_all_sessions(db: &MySqlPool, user_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"UPDATE login SET token_version = token_version + 1 WHERE id = ?"#)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    sqlx::query(r#"UPDATE refresh_tokens SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    sqlx::query(r#"UPDATE sessions SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::MySqlPool;
+
+use crate::model::SessionModel;
+
+use super::{audit::ClientInfo, refresh::revoke_refresh_family};
+
+// last_seen_at is only written when it is older than this, not on every request
+const LAST_SEEN_RESOLUTION_SECONDS: i64 = 60;
+
+pub async fn create_session(
+    db: &MySqlPool,
+    id: &str,
+    user_id: i64,
+    client: &ClientInfo,
+    expires_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"INSERT INTO sessions (id, user_id, ip, user_agent, last_seen_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)"#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(&client.ip)
+    .bind(&client.user_agent)
+    .bind(Utc::now())
+    .bind(expires_at)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn find_session(db: &MySqlPool, id: &str) -> Result<Option<SessionModel>, sqlx::Error> {
+    sqlx::query_as::<_, SessionModel>(r#"SELECT * FROM sessions WHERE id = ?"#)
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Sessions that haven't been revoked or run out, most recently used first
+pub async fn active_sessions(db: &MySqlPool, user_id: i64) -> Result<Vec<SessionModel>, sqlx::Error> {
+    sqlx::query_as::<_, SessionModel>(
+        r#"SELECT * FROM sessions WHERE user_id = ? AND revoked_at IS NULL AND expires_at > ? ORDER BY last_seen_at DESC"#,
+    )
+    .bind(user_id)
+    .bind(Utc::now())
+    .fetch_all(db)
+    .await
+}
+
+pub async fn touch_session(db: &MySqlPool, session: &SessionModel) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    if now - session.last_seen_at < Duration::seconds(LAST_SEEN_RESOLUTION_SECONDS) {
+        return Ok(());
+    }
+    sqlx::query(r#"UPDATE sessions SET last_seen_at = ? WHERE id = ?"#)
+        .bind(now)
+        .bind(&session.id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+// After a refresh: the session lives as long as its newest refresh token
+pub async fn extend_session(db: &MySqlPool, id: &str, expires_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"UPDATE sessions SET last_seen_at = ?, expires_at = ? WHERE id = ?"#)
+        .bind(Utc::now())
+        .bind(expires_at)
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+// Ends a single session: its refresh tokens stop working right away, auth_guard
+// refuses its access tokens from the next request on. Returns false when it
+// was already revoked.
+pub async fn revoke_session(db: &MySqlPool, id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(r#"UPDATE sessions SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL"#)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(db)
+        .await?;
+    revoke_refresh_family(db, id).await?;
+
+    Ok(result.rows_affected() == 1)
+}
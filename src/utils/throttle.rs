@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::config::Config;
+
+#[derive(Debug)]
+struct Attempts {
+    failures: u32,
+    last_failure: Instant,
+    blocked_until: Instant,
+}
+
+// In-memory failed login tracking, keyed per account and per client IP.
+// Every failure doubles the wait before the next attempt is accepted
+// (backoff, 2 * backoff, 4 * backoff, ...); reaching the attempt limit locks
+// the key for the whole lockout period. A successful login resets the account.
+pub struct LoginThrottle {
+    attempts: Mutex<HashMap<String, Attempts>>,
+    max_account_attempts: u32,
+    max_ip_attempts: u32,
+    backoff: Duration,
+    lockout: Duration,
+}
+
+impl LoginThrottle {
+    pub fn new(config: &Config) -> Self {
+        LoginThrottle {
+            attempts: Mutex::new(HashMap::new()),
+            max_account_attempts: config.login_max_attempts,
+            max_ip_attempts: config.login_ip_max_attempts,
+            backoff: Duration::from_secs(config.login_backoff_seconds),
+            lockout: Duration::from_secs(config.login_lockout_seconds),
+        }
+    }
+
+    // Err holds how long the caller has to wait before trying again
+    pub fn check(&self, email: &str, ip: &str) -> Result<(), Duration> {
+        let attempts = self.attempts.lock().unwrap();
+        let now = Instant::now();
+        let wait = [account_key(email), ip_key(ip)]
+            .iter()
+            .filter_map(|key| attempts.get(key))
+            .map(|entry| entry.blocked_until.saturating_duration_since(now))
+            .max()
+            .unwrap_or_default();
+
+        if wait.is_zero() {
+            Ok(())
+        } else {
+            Err(wait)
+        }
+    }
+
+    pub fn record_failure(&self, email: &str, ip: &str) {
+        let mut attempts = self.attempts.lock().unwrap();
+        let now = Instant::now();
+        // failures older than the lockout period are forgotten
+        attempts.retain(|_, entry| now.duration_since(entry.last_failure) < self.lockout);
+
+        for (key, max_attempts) in [
+            (account_key(email), self.max_account_attempts),
+            (ip_key(ip), self.max_ip_attempts),
+        ] {
+            let entry = attempts.entry(key).or_insert(Attempts {
+                failures: 0,
+                last_failure: now,
+                blocked_until: now,
+            });
+            entry.failures += 1;
+            entry.last_failure = now;
+            entry.blocked_until = now + self.delay_after(entry.failures, max_attempts);
+        }
+    }
+
+    pub fn record_success(&self, email: &str) {
+        self.attempts.lock().unwrap().remove(&account_key(email));
+    }
+
+    fn delay_after(&self, failures: u32, max_attempts: u32) -> Duration {
+        if failures >= max_attempts {
+            return self.lockout;
+        }
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.lockout)
+    }
+}
+
+fn account_key(email: &str) -> String {
+    format!("account:{}", email.to_ascii_lowercase())
+}
+
+fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle {
+            attempts: Mutex::new(HashMap::new()),
+            max_account_attempts: 5,
+            max_ip_attempts: 20,
+            backoff: Duration::from_secs(1),
+            lockout: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn delay_doubles_with_every_failure() {
+        let throttle = throttle();
+        let delays: Vec<u64> = (1..=4).map(|failures| throttle.delay_after(failures, 5).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn delay_is_the_lockout_from_the_attempt_limit_on() {
+        let throttle = throttle();
+        assert_eq!(throttle.delay_after(5, 5), Duration::from_secs(60));
+        assert_eq!(throttle.delay_after(6, 5), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn delay_never_exceeds_the_lockout() {
+        let throttle = throttle();
+        assert_eq!(throttle.delay_after(19, 20), Duration::from_secs(60));
+        assert_eq!(throttle.delay_after(u32::MAX - 1, u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn failure_blocks_the_account_and_the_ip() {
+        let throttle = throttle();
+        throttle.record_failure("Someone@Example.com", "10.0.0.1");
+
+        assert!(throttle.check("someone@example.com", "10.0.0.2").is_err());
+        assert!(throttle.check("other@example.com", "10.0.0.1").is_err());
+        assert!(throttle.check("other@example.com", "10.0.0.2").is_ok());
+    }
+
+    #[test]
+    fn success_only_resets_the_account() {
+        let throttle = throttle();
+        throttle.record_failure("someone@example.com", "10.0.0.1");
+        throttle.record_success("someone@example.com");
+
+        assert!(throttle.check("someone@example.com", "10.0.0.2").is_ok());
+        assert!(throttle.check("someone@example.com", "10.0.0.1").is_err());
+    }
+}
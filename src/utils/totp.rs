@@ -0,0 +1,117 @@
+use chrono::Utc;
+use rand::RngCore;
+use sqlx::MySqlPool;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::model::LoginModel;
+
+use super::token::hash_token;
+
+const DIGITS: usize = 6;
+const STEP: u64 = 30;
+// one step either side of the current one is accepted, for clock drift
+const SKEW: u64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+// New random 160 bit secret, base32 encoded the way authenticator apps expect it
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+}
+
+fn totp(secret: &str, issuer: &str, account_name: &str) -> Option<TOTP> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    TOTP::new(
+        Algorithm::SHA1,
+        DIGITS,
+        SKEW as u8,
+        STEP,
+        secret,
+        Some(issuer.to_string()),
+        account_name.to_string(),
+    )
+    .ok()
+}
+
+// otpauth:// URI to render as a QR code during enrollment
+pub fn provisioning_uri(secret: &str, issuer: &str, account_name: &str) -> Option<String> {
+    totp(secret, issuer, account_name).map(|totp| totp.get_url())
+}
+
+// Returns the time step the code belongs to. Callers store it and pass it back
+// as `last_step` so a code can't be used twice.
+pub fn verify_code(secret: &str, code: &str, last_step: i64, now: u64) -> Option<i64> {
+    let totp = totp(secret, "", "")?;
+    let current = now / STEP;
+    (current.saturating_sub(SKEW)..=current + SKEW)
+        .filter(|step| *step as i64 > last_step)
+        .find(|step| totp.generate(step * STEP) == code.trim())
+        .map(|step| step as i64)
+}
+
+// One-time codes for when the authenticator is lost, formatted as xxxxx-xxxxx
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let code = hex::encode(bytes);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+// Checks a code against the user's enabled secret and records its time step
+pub async fn consume_totp_code(db: &MySqlPool, user: &LoginModel, code: &str) -> Result<bool, sqlx::Error> {
+    let secret = match (&user.totp_secret, user.totp_enabled_at) {
+        (Some(secret), Some(_)) => secret,
+        _ => return Ok(false),
+    };
+    let step = match verify_code(secret, code, user.totp_last_step, Utc::now().timestamp() as u64) {
+        Some(step) => step,
+        None => return Ok(false),
+    };
+
+    // guards against the same code being accepted by two concurrent requests
+    let result = sqlx::query(r#"UPDATE login SET totp_last_step = ? WHERE id = ? AND totp_last_step < ?"#)
+        .bind(step)
+        .bind(user.id)
+        .bind(step)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn consume_recovery_code(db: &MySqlPool, user_id: i64, code: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"UPDATE totp_recovery_codes SET used_at = ? WHERE user_id = ? AND code_hash = ? AND used_at IS NULL LIMIT 1"#,
+    )
+    .bind(Utc::now())
+    .bind(user_id)
+    .bind(hash_token(&code.trim().to_ascii_lowercase()))
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// Replaces all recovery codes of the user and returns the new ones in clear text
+pub async fn replace_recovery_codes(db: &MySqlPool, user_id: i64) -> Result<Vec<String>, sqlx::Error> {
+    let codes = generate_recovery_codes();
+    let mut tx = db.begin().await?;
+
+    sqlx::query(r#"DELETE FROM totp_recovery_codes WHERE user_id = ?"#)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    for code in &codes {
+        sqlx::query(r#"INSERT INTO totp_recovery_codes (user_id, code_hash) VALUES (?, ?)"#)
+            .bind(user_id)
+            .bind(hash_token(code))
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(codes)
+}